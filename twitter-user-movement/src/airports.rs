@@ -0,0 +1,168 @@
+use geo::prelude::GeodesicDistance;
+use geo_types::Point;
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize, Serializer};
+use std::path::Path;
+use uom::si::f64::Length;
+use uom::si::length::meter;
+
+/// the airport types of the OurAirports data set used for snapping. Small airfields and
+/// heliports would catch too many points of ground movements.
+const AIRPORT_TYPES: [&str; 2] = ["large_airport", "medium_airport"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Airport {
+    /// the IATA code when available, the identifier otherwise
+    pub code: String,
+    pub name: String,
+    pub point: Point<f64>,
+}
+
+impl Serialize for Airport {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("Airport", 4)?;
+        state.serialize_field("code", &self.code)?;
+        state.serialize_field("name", &self.name)?;
+        state.serialize_field("x", &self.point.x())?;
+        state.serialize_field("y", &self.point.y())?;
+        state.end()
+    }
+}
+
+/// a row of the CSV file, accepting the columns of the OurAirports data set or plain
+/// `code,name,latitude,longitude` columns
+#[derive(Deserialize)]
+struct AirportRecord {
+    #[serde(default)]
+    code: Option<String>,
+    #[serde(default)]
+    iata_code: Option<String>,
+    #[serde(default)]
+    ident: Option<String>,
+    #[serde(default, rename = "type")]
+    airport_type: Option<String>,
+    name: String,
+    #[serde(alias = "latitude_deg")]
+    latitude: f64,
+    #[serde(alias = "longitude_deg")]
+    longitude: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Airports {
+    pub source: String,
+    airports: Vec<Airport>,
+
+    /// points further away from the nearest airport are not snapped
+    pub max_snap_distance: Length,
+}
+
+impl Airports {
+    pub fn new(source: String, airports: Vec<Airport>, max_snap_distance: Length) -> Self {
+        Self {
+            source,
+            airports,
+            max_snap_distance,
+        }
+    }
+
+    /// load the airports of a CSV file, for example `airports.csv` of OurAirports
+    pub fn from_csv_file<P: AsRef<Path>>(path: P, max_snap_distance: Length) -> eyre::Result<Self> {
+        let mut reader = csv::Reader::from_path(path.as_ref())?;
+        let mut airports = Vec::new();
+        for record in reader.deserialize() {
+            let record: AirportRecord = record?;
+            if let Some(airport_type) = record.airport_type.as_deref() {
+                if !AIRPORT_TYPES.contains(&airport_type) {
+                    continue;
+                }
+            }
+            let code = [record.iata_code, record.code, record.ident]
+                .into_iter()
+                .flatten()
+                .find(|code| !code.is_empty())
+                .unwrap_or_default();
+            airports.push(Airport {
+                code,
+                name: record.name,
+                point: Point::new(record.longitude, record.latitude),
+            });
+        }
+        if airports.is_empty() {
+            return Err(eyre::eyre!(
+                "{} does not contain any airports",
+                path.as_ref().display()
+            ));
+        }
+        Ok(Self::new(
+            path.as_ref().display().to_string(),
+            airports,
+            max_snap_distance,
+        ))
+    }
+
+    pub fn len(&self) -> usize {
+        self.airports.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.airports.is_empty()
+    }
+
+    /// the airport closest to `point` within the `max_snap_distance`
+    pub fn nearest(&self, point: &Point<f64>) -> Option<&Airport> {
+        let max_distance_m = self.max_snap_distance.get::<meter>();
+        self.airports
+            .iter()
+            .map(|airport| (airport, point.geodesic_distance(&airport.point)))
+            .filter(|(_, distance)| *distance <= max_distance_m)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(airport, _)| airport)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Airports;
+    use geo_types::Point;
+    use serde_json::json;
+    use std::io::Write;
+    use uom::si::f64::Length;
+    use uom::si::length::kilometer;
+
+    #[test]
+    fn read_ourairports_csv() {
+        let mut f = tempfile::NamedTempFile::new().unwrap();
+        writeln!(
+            f,
+            "id,ident,type,name,latitude_deg,longitude_deg,iata_code\n\
+             1,EDDB,large_airport,\"Berlin Brandenburg Airport\",52.351389,13.493889,BER\n\
+             2,EDAZ,small_airport,\"Schönhagen, Trebbin\",52.203889,13.158611,\n\
+             3,EDDH,large_airport,Hamburg Airport,53.630389,9.988228,HAM"
+        )
+        .unwrap();
+        let airports = Airports::from_csv_file(f.path(), Length::new::<kilometer>(50.0)).unwrap();
+        assert_eq!(airports.len(), 2);
+
+        // Berlin Alexanderplatz
+        let nearest = airports.nearest(&Point::new(13.413, 52.522)).unwrap();
+        assert_eq!(nearest.code, "BER");
+        assert_eq!(nearest.name, "Berlin Brandenburg Airport");
+
+        // Leipzig
+        assert!(airports.nearest(&Point::new(12.374, 51.340)).is_none());
+
+        assert_eq!(
+            serde_json::to_value(nearest).unwrap(),
+            json!({
+                "code": "BER",
+                "name": "Berlin Brandenburg Airport",
+                "x": 13.493889,
+                "y": 52.351389,
+            })
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use geo_types::{CoordFloat, Coordinate, LineString};
+use geo_types::{Coord, CoordFloat, LineString};
 use nalgebra::{ComplexField, RealField, Vector2};
 
 pub trait Angles {
@@ -8,7 +8,7 @@ pub trait Angles {
 }
 
 /// compute the angles between the coordinates using a three-coordinates wide sliding window.
-impl<T> Angles for [Coordinate<T>]
+impl<T> Angles for [Coord<T>]
 where
     T: CoordFloat + RealField,
 {
@@ -38,7 +38,7 @@ where
     }
 }
 
-pub fn angle_radians<T>(coords: &[Coordinate<T>; 3]) -> T
+pub fn angle_radians<T>(coords: &[Coord<T>; 3]) -> T
 where
     T: CoordFloat + RealField,
 {
@@ -49,7 +49,7 @@ where
     ComplexField::acos(v2d_a.dot(&v2d_b) / (v2d_a.magnitude() * v2d_b.magnitude()))
 }
 
-fn angles_radians<T, C>(coord_sequence: &[Coordinate<T>], transform: C) -> Vec<T>
+fn angles_radians<T, C>(coord_sequence: &[Coord<T>], transform: C) -> Vec<T>
 where
     T: CoordFloat + RealField,
     C: Fn(T) -> T,
@@ -1,6 +1,6 @@
 use crate::algo::angle::angle_radians;
 use geo::prelude::GeodesicDistance;
-use geo_types::{Coordinate, Point};
+use geo_types::{Coord, Point};
 use uom::si::f64::Length;
 use uom::si::length::meter;
 
@@ -14,7 +14,7 @@ pub trait Curviness {
     }
 }
 
-impl Curviness for [Coordinate<f64>] {
+impl Curviness for [Coord<f64>] {
     fn curviness(&self) -> Vec<f64> {
         // TODO: making this an iterator
 
@@ -32,7 +32,7 @@ impl Curviness for [Coordinate<f64>] {
     }
 }
 
-fn geodesic_distance_covered(coords: &[Coordinate<f64>]) -> Length {
+fn geodesic_distance_covered(coords: &[Coord<f64>]) -> Length {
     Length::new::<meter>(
         coords
             .windows(2)
@@ -59,7 +59,7 @@ mod tests {
 
          */
 
-        let coords = vec![
+        let coords = [
             coord!(x: 10., y:10.),
             coord!(x: 15., y:11.),
             coord!(x: 20., y:9.),
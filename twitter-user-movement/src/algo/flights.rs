@@ -0,0 +1,193 @@
+//! Detection of flights
+//!
+//! Segments covering a long distance at a speed no ground transport reaches are flights.
+//! Both thresholds are checked against the lower bound of the speed and distance, so coarse
+//! places do not turn a train ride into a flight. The endpoints of a flight are snapped to
+//! the nearest airports when a list of airports is given.
+
+use crate::airports::{Airport, Airports};
+use crate::algo::speed::{speed_bounded, SpeedBound};
+use crate::algo::transport_mode::{ModeEstimate, TransportMode};
+use crate::algo::PointInTime;
+use chrono::{DateTime, Utc};
+use geo::prelude::GeodesicDistance;
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use std::sync::Arc;
+use uom::si::f64::{Length, Velocity};
+use uom::si::length::{kilometer, meter};
+use uom::si::velocity::kilometer_per_hour;
+
+#[derive(Debug, Clone)]
+pub struct FlightOptions {
+    /// the minimum speed of a flight
+    pub min_speed: Velocity,
+    /// the minimum distance covered by a flight
+    pub min_distance: Length,
+    /// snap the endpoints of the flights to these airports
+    pub airports: Option<Arc<Airports>>,
+    /// leave the flights out of the speed statistics, which then only describe ground
+    /// transport
+    pub exclude_from_speeds: bool,
+}
+
+impl Default for FlightOptions {
+    fn default() -> Self {
+        Self {
+            min_speed: Velocity::new::<kilometer_per_hour>(250.0),
+            min_distance: Length::new::<kilometer>(100.0),
+            airports: None,
+            exclude_from_speeds: false,
+        }
+    }
+}
+
+impl Serialize for FlightOptions {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("FlightOptions", 4)?;
+        state.serialize_field("min_speed_kmh", &self.min_speed.get::<kilometer_per_hour>())?;
+        state.serialize_field("min_distance_km", &self.min_distance.get::<kilometer>())?;
+        state.serialize_field(
+            "airports",
+            &self
+                .airports
+                .as_ref()
+                .map(|airports| airports.source.as_str()),
+        )?;
+        state.serialize_field("exclude_from_speeds", &self.exclude_from_speeds)?;
+        state.end()
+    }
+}
+
+impl FlightOptions {
+    /// true when the segment from `tp1` to `tp2` is a flight
+    pub fn is_flight<PIT: PointInTime>(&self, tp1: &PIT, tp2: &PIT) -> bool {
+        let distance = Length::new::<meter>(tp1.point().geodesic_distance(&tp2.point()))
+            - tp1.uncertainty()
+            - tp2.uncertainty();
+        distance >= self.min_distance
+            && speed_bounded(tp1, tp2, SpeedBound::Lower)
+                .map(|speed| speed >= self.min_speed)
+                .unwrap_or(false)
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Flight {
+    /// the index of the segment, the flight starts at the point with this index
+    pub segment: usize,
+    pub departure: DateTime<Utc>,
+    pub arrival: DateTime<Utc>,
+    pub origin: Option<Airport>,
+    pub destination: Option<Airport>,
+}
+
+pub trait Flights {
+    /// true for each segment between consecutive points which is a flight
+    ///
+    /// expects the points to be sorted chronologically
+    fn flight_segments(&self, options: &FlightOptions) -> Vec<bool>;
+
+    /// the flights together with the airports nearest to their endpoints
+    ///
+    /// expects the points to be sorted chronologically
+    fn flights(&self, options: &FlightOptions) -> Vec<Flight>;
+}
+
+impl<PIT> Flights for [PIT]
+where
+    PIT: PointInTime,
+{
+    fn flight_segments(&self, options: &FlightOptions) -> Vec<bool> {
+        self.windows(2)
+            .map(|window| options.is_flight(&window[0], &window[1]))
+            .collect()
+    }
+
+    fn flights(&self, options: &FlightOptions) -> Vec<Flight> {
+        let nearest_airport = |pit: &PIT| {
+            options
+                .airports
+                .as_ref()
+                .and_then(|airports| airports.nearest(&pit.point()))
+                .cloned()
+        };
+        self.flight_segments(options)
+            .iter()
+            .enumerate()
+            .filter(|(_, is_flight)| **is_flight)
+            .map(|(segment, _)| Flight {
+                segment,
+                departure: self[segment].timestamp(),
+                arrival: self[segment + 1].timestamp(),
+                origin: nearest_airport(&self[segment]),
+                destination: nearest_airport(&self[segment + 1]),
+            })
+            .collect()
+    }
+}
+
+/// label the modes of the flight segments as [`TransportMode::Flight`] with full confidence
+pub fn label_flights(modes: &mut [Option<ModeEstimate>], flight_segments: &[bool]) {
+    for (mode, is_flight) in modes.iter_mut().zip(flight_segments) {
+        if *is_flight {
+            *mode = Some(ModeEstimate {
+                mode: TransportMode::Flight,
+                confidence: 1.0,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FlightOptions, Flights};
+    use crate::airports::{Airport, Airports};
+    use crate::algo::tests::MyPit;
+    use chrono::{DateTime, Utc};
+    use geo_types::Point;
+    use std::sync::Arc;
+    use uom::si::f64::Length;
+    use uom::si::length::kilometer;
+
+    #[test]
+    fn detect_flights() {
+        // Berlin to Hamburg: by car, by plane and by train
+        let points = [
+            (13.41, 52.52, 0),
+            (13.45, 52.37, 3600),
+            (9.99, 53.63, 7200),
+            (13.37, 52.52, 7200 + 3 * 3600),
+        ]
+        .iter()
+        .map(|(x, y, ts)| MyPit {
+            p: Point::new(*x, *y),
+            ts: DateTime::<Utc>::from_timestamp(*ts, 0).unwrap(),
+        })
+        .collect::<Vec<_>>();
+
+        let airport = |code: &str, x: f64, y: f64| Airport {
+            code: code.to_string(),
+            name: code.to_string(),
+            point: Point::new(x, y),
+        };
+        let options = FlightOptions {
+            airports: Some(Arc::new(Airports::new(
+                "test".to_string(),
+                vec![airport("BER", 13.49, 52.35), airport("HAM", 9.99, 53.63)],
+                Length::new::<kilometer>(20.0),
+            ))),
+            ..Default::default()
+        };
+        assert_eq!(points.flight_segments(&options), vec![false, true, false]);
+
+        let flights = points.flights(&options);
+        assert_eq!(flights.len(), 1);
+        assert_eq!(flights[0].segment, 1);
+        assert_eq!(flights[0].origin.as_ref().unwrap().code, "BER");
+        assert_eq!(flights[0].destination.as_ref().unwrap().code, "HAM");
+    }
+}
@@ -0,0 +1,163 @@
+//! Inference of the home and work locations of a user
+//!
+//! The home is the most frequent location at night and on weekends, the work location the
+//! most frequent location during weekday office hours. The local time of a point is
+//! approximated by the mean solar time at its longitude, as the time zone of the user is
+//! not known.
+
+use crate::algo::PointInTime;
+use chrono::{DateTime, Datelike, Duration, Timelike, Utc, Weekday};
+use geo::algorithm::geodesic_distance::GeodesicDistance;
+use geo_types::Point;
+use uom::si::f64::Length;
+use uom::si::length::meter;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HomeWorkOptions {
+    /// the maximum distance of points from the center of a location
+    pub radius: Length,
+    /// the minimum number of points supporting a location
+    pub min_points: usize,
+}
+
+impl Default for HomeWorkOptions {
+    fn default() -> Self {
+        Self {
+            radius: Length::new::<meter>(300.0),
+            min_points: 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InferredLocation {
+    /// the centroid of the supporting points
+    pub point: Point<f64>,
+    /// the share of the candidate points supporting the location, between 0 and 1
+    pub confidence: f64,
+    /// the number of points supporting the location
+    pub point_count: usize,
+}
+
+pub trait HomeWork {
+    /// the most frequent location at night (22:00 to 06:00) and on weekends
+    fn home(&self, options: &HomeWorkOptions) -> Option<InferredLocation>;
+
+    /// the most frequent location on weekdays from 09:00 to 17:00
+    fn work(&self, options: &HomeWorkOptions) -> Option<InferredLocation>;
+}
+
+impl<PIT> HomeWork for [PIT]
+where
+    PIT: PointInTime,
+{
+    fn home(&self, options: &HomeWorkOptions) -> Option<InferredLocation> {
+        most_frequent_location(
+            self.iter().filter_map(|pit| {
+                let local = local_time(pit);
+                let is_night = local.hour() >= 22 || local.hour() < 6;
+                (is_night || is_weekend(&local)).then(|| pit.point())
+            }),
+            options,
+        )
+    }
+
+    fn work(&self, options: &HomeWorkOptions) -> Option<InferredLocation> {
+        most_frequent_location(
+            self.iter().filter_map(|pit| {
+                let local = local_time(pit);
+                (!is_weekend(&local) && (9..17).contains(&local.hour())).then(|| pit.point())
+            }),
+            options,
+        )
+    }
+}
+
+/// the mean solar time at the longitude of the point
+fn local_time<PIT: PointInTime>(pit: &PIT) -> DateTime<Utc> {
+    pit.timestamp() + Duration::seconds((pit.point().x() / 15.0 * 3600.0).round() as i64)
+}
+
+fn is_weekend(local: &DateTime<Utc>) -> bool {
+    matches!(local.weekday(), Weekday::Sat | Weekday::Sun)
+}
+
+/// cluster the points greedily, each point is assigned to the first cluster within the radius
+fn most_frequent_location<I>(points: I, options: &HomeWorkOptions) -> Option<InferredLocation>
+where
+    I: Iterator<Item = Point<f64>>,
+{
+    let radius_m = options.radius.get::<meter>();
+    // centroid and number of points
+    let mut clusters: Vec<(Point<f64>, usize)> = Vec::new();
+    let mut candidates = 0;
+    for point in points {
+        candidates += 1;
+        match clusters
+            .iter_mut()
+            .find(|(center, _)| center.geodesic_distance(&point) <= radius_m)
+        {
+            Some((center, n)) => {
+                let nf = *n as f64;
+                *center = Point::new(
+                    (center.x() * nf + point.x()) / (nf + 1.0),
+                    (center.y() * nf + point.y()) / (nf + 1.0),
+                );
+                *n += 1;
+            }
+            None => clusters.push((point, 1)),
+        }
+    }
+    clusters
+        .into_iter()
+        // the earliest cluster wins ties
+        .rev()
+        .max_by_key(|(_, n)| *n)
+        .filter(|(_, n)| *n >= options.min_points.max(1))
+        .map(|(point, n)| InferredLocation {
+            point,
+            confidence: n as f64 / candidates as f64,
+            point_count: n,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HomeWork, HomeWorkOptions};
+    use crate::algo::tests::MyPit;
+    use chrono::{TimeZone, Utc};
+    use geo_types::Point;
+
+    #[test]
+    fn infer_home_and_work() {
+        // 2020-09-14 is a monday. At 30 degrees east the local time is two hours ahead of UTC.
+        let points = [
+            ((14, 21), 30.0), // night, home
+            ((15, 7), 30.1),  // weekday morning, work
+            ((15, 12), 30.1), // weekday afternoon, work
+            ((15, 22), 30.0), // night, home
+            ((16, 9), 30.2),  // weekday, elsewhere
+            ((19, 10), 30.0), // saturday, home
+            ((20, 10), 30.3), // sunday, elsewhere
+        ]
+        .iter()
+        .map(|((day, hour), x)| MyPit {
+            p: Point::new(*x, 50.0),
+            ts: Utc.with_ymd_and_hms(2020, 9, *day, *hour, 0, 0).unwrap(),
+        })
+        .collect::<Vec<_>>();
+        let options = HomeWorkOptions::default();
+
+        let home = points.home(&options).unwrap();
+        assert_eq!(home.point, Point::new(30.0, 50.0));
+        assert_eq!(home.point_count, 3);
+        assert!((home.confidence - 0.75).abs() < 1e-9);
+
+        let work = points.work(&options).unwrap();
+        assert_eq!(work.point, Point::new(30.1, 50.0));
+        assert_eq!(work.point_count, 2);
+        assert!((work.confidence - 2.0 / 3.0).abs() < 1e-9);
+
+        assert!(points[4..].work(&options).is_none());
+    }
+}
@@ -1,18 +1,67 @@
 use chrono::{DateTime, Utc};
 use geo_types::Point;
+use uom::si::f64::Length;
+use uom::si::length::meter;
 
 pub mod angle;
 pub mod curviness;
+pub mod flights;
+pub mod home_work;
+pub mod outliers;
+pub mod segmentation;
 pub mod speed;
+pub mod staypoints;
 pub mod straightness;
 pub mod time;
+pub mod transport_mode;
 
 pub use angle::Angles;
 pub use curviness::Curviness;
+pub use flights::Flights;
+pub use home_work::HomeWork;
+pub use outliers::RemoveOutliers;
+pub use segmentation::Segmentation;
 pub use speed::Speed;
+pub use staypoints::StayPoints;
+pub use straightness::{Straightness, StraightnessChunked};
 pub use time::SortChronologically;
+pub use transport_mode::TransportModes;
 
 pub trait PointInTime {
     fn timestamp(&self) -> DateTime<Utc>;
     fn point(&self) -> Point<f64>;
+
+    /// radius around the point within which the actual position is located
+    fn uncertainty(&self) -> Length {
+        Length::new::<meter>(0.0)
+    }
+
+    /// whether the timestamp has millisecond precision. Second precision otherwise.
+    fn has_exact_timestamp(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::PointInTime;
+    use chrono::{DateTime, Utc};
+    use geo_types::Point;
+
+    /// a point in time for the tests of the algorithms
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct MyPit {
+        pub p: Point<f64>,
+        pub ts: DateTime<Utc>,
+    }
+
+    impl PointInTime for MyPit {
+        fn timestamp(&self) -> DateTime<Utc> {
+            self.ts
+        }
+
+        fn point(&self) -> Point<f64> {
+            self.p
+        }
+    }
 }
@@ -0,0 +1,172 @@
+use crate::algo::speed::{speed_bounded, SpeedBound};
+use crate::algo::PointInTime;
+use uom::si::f64::Velocity;
+
+/// the maximum number of consecutive bad points recognized as a spike
+const MAX_SPIKE_POINTS: usize = 3;
+
+pub trait RemoveOutliers {
+    /// remove points which can only be reached with a speed above `max_speed`, for example
+    /// caused by a single bad coordinate. Returns the number of removed points.
+    ///
+    /// When the speed between two consecutive points is implausible, the following and the
+    /// preceding points decide which of both is removed: the points which can be skipped to
+    /// connect their neighbours with a plausible speed. Jumps which can not be attributed to
+    /// up to three consecutive points are kept, as are jumps to consistent points at the end.
+    ///
+    /// The lower bound of the speeds is used, so the uncertainty of the points is not
+    /// mistaken for a jump.
+    ///
+    /// expects the points to be sorted chronologically
+    fn remove_outliers(&mut self, max_speed: Velocity) -> usize;
+}
+
+impl<PIT> RemoveOutliers for Vec<PIT>
+where
+    PIT: PointInTime,
+{
+    fn remove_outliers(&mut self, max_speed: Velocity) -> usize {
+        let plausible = |a: usize, b: usize| {
+            // undefined speeds can not be judged
+            speed_bounded(&self[a], &self[b], SpeedBound::Lower)
+                .map(|speed| speed <= max_speed)
+                .unwrap_or(true)
+        };
+
+        // indexes of the points to keep
+        let mut kept: Vec<usize> = Vec::with_capacity(self.len());
+        let mut idx = 0;
+        while idx < self.len() {
+            let prev = match kept.last() {
+                Some(prev) => *prev,
+                None => {
+                    kept.push(idx);
+                    idx += 1;
+                    continue;
+                }
+            };
+            if plausible(prev, idx) {
+                kept.push(idx);
+                idx += 1;
+                continue;
+            }
+
+            // forward: the current point starts a spike when one of the next points
+            // can be reached from the previous point
+            if let Some(next) = (idx + 1..(idx + 1 + MAX_SPIKE_POINTS).min(self.len()))
+                .find(|j| plausible(prev, *j))
+            {
+                idx = next;
+                continue;
+            }
+
+            // backward: the previous point is a spike when the current point can be reached
+            // from the point before it
+            let prev_is_spike = match kept.len() {
+                1 => idx + 1 < self.len() && plausible(idx, idx + 1),
+                n => plausible(kept[n - 2], idx),
+            };
+            if prev_is_spike {
+                kept.pop();
+                continue;
+            }
+
+            // the last points jump away from a consistent trajectory, unless they are
+            // consistent with each other
+            let tail_is_spike = self.len() - idx <= MAX_SPIKE_POINTS
+                && kept.len() > 1
+                && (self.len() - idx == 1 || !(idx..self.len() - 1).all(|j| plausible(j, j + 1)));
+            if tail_is_spike {
+                break;
+            }
+            // a jump which can not be attributed to one of the points
+            kept.push(idx);
+            idx += 1;
+        }
+
+        let removed = self.len() - kept.len();
+        if removed > 0 {
+            let mut kept = kept.into_iter().peekable();
+            let mut idx = 0;
+            self.retain(|_| {
+                let keep = kept.peek() == Some(&idx);
+                if keep {
+                    kept.next();
+                }
+                idx += 1;
+                keep
+            });
+        }
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RemoveOutliers;
+    use crate::algo::tests::MyPit;
+    use chrono::{DateTime, Utc};
+    use geo_types::Point;
+    use uom::si::f64::Velocity;
+    use uom::si::velocity::kilometer_per_hour;
+
+    fn xs_after_cleaning(xs: &[f64]) -> (Vec<f64>, usize) {
+        // one point per hour, 0.1 degrees at the equator are about 11 km
+        let mut points = xs
+            .iter()
+            .enumerate()
+            .map(|(i, x)| MyPit {
+                p: Point::new(*x, 0.0),
+                ts: DateTime::<Utc>::from_timestamp(i as i64 * 3600, 0).unwrap(),
+            })
+            .collect::<Vec<_>>();
+        let removed = points.remove_outliers(Velocity::new::<kilometer_per_hour>(1000.0));
+        (points.iter().map(|p| p.p.x()).collect(), removed)
+    }
+
+    #[test]
+    fn remove_spikes() {
+        // a single bad coordinate in between
+        assert_eq!(
+            xs_after_cleaning(&[0.0, 0.1, 90.0, 0.2, 0.3]),
+            (vec![0.0, 0.1, 0.2, 0.3], 1)
+        );
+        // at the start and at the end
+        assert_eq!(
+            xs_after_cleaning(&[90.0, 0.1, 0.2, 0.3]),
+            (vec![0.1, 0.2, 0.3], 1)
+        );
+        assert_eq!(
+            xs_after_cleaning(&[0.0, 0.1, 0.2, 90.0]),
+            (vec![0.0, 0.1, 0.2], 1)
+        );
+        // two consecutive bad coordinates
+        assert_eq!(
+            xs_after_cleaning(&[0.0, 0.1, 90.0, 90.1, 0.2]),
+            (vec![0.0, 0.1, 0.2], 2)
+        );
+        // plausible movements are kept
+        assert_eq!(
+            xs_after_cleaning(&[0.0, 5.0, 10.0]),
+            (vec![0.0, 5.0, 10.0], 0)
+        );
+        // a consistent trajectory after the jump
+        assert_eq!(
+            xs_after_cleaning(&[0.0, 0.1, 90.0, 90.1, 90.2, 90.3, 90.4]).1,
+            0
+        );
+        // consistent points after a jump at the end
+        assert_eq!(
+            xs_after_cleaning(&[0.0, 0.1, 0.2, 0.3, 90.0, 90.1, 90.2]).1,
+            0
+        );
+        assert_eq!(xs_after_cleaning(&[0.0, 0.1, 0.2, 0.3, 90.0, 90.1]).1, 0);
+        // inconsistent points at the end
+        assert_eq!(
+            xs_after_cleaning(&[0.0, 0.1, 0.2, 0.3, 90.0, -90.0]),
+            (vec![0.0, 0.1, 0.2, 0.3], 2)
+        );
+        // not attributable to one of the points
+        assert_eq!(xs_after_cleaning(&[0.0, 90.0]), (vec![0.0, 90.0], 0));
+    }
+}
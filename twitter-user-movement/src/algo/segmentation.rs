@@ -0,0 +1,124 @@
+use crate::algo::PointInTime;
+use chrono::Duration;
+use geo::algorithm::geodesic_distance::GeodesicDistance;
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use uom::si::f64::Length;
+use uom::si::length::{kilometer, meter};
+
+/// thresholds ending a trip between two consecutive points
+#[derive(Debug, Clone, PartialEq)]
+pub struct SegmentationOptions {
+    /// the maximum time between two points of the same trip
+    pub max_gap: Duration,
+    /// the maximum distance between two points of the same trip
+    pub max_distance: Option<Length>,
+}
+
+impl Default for SegmentationOptions {
+    fn default() -> Self {
+        Self {
+            max_gap: Duration::hours(6),
+            max_distance: None,
+        }
+    }
+}
+
+impl Serialize for SegmentationOptions {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("SegmentationOptions", 2)?;
+        state.serialize_field("max_gap_s", &self.max_gap.num_seconds())?;
+        state.serialize_field(
+            "max_distance_km",
+            &self.max_distance.map(|l| l.get::<kilometer>()),
+        )?;
+        state.end()
+    }
+}
+
+impl SegmentationOptions {
+    /// true when `tp1` and `tp2` do not belong to the same trip
+    fn is_break<PIT: PointInTime>(&self, tp1: &PIT, tp2: &PIT) -> bool {
+        if tp2.timestamp() - tp1.timestamp() > self.max_gap {
+            return true;
+        }
+        self.max_distance
+            .map(|max_distance| {
+                Length::new::<meter>(tp1.point().geodesic_distance(&tp2.point())) > max_distance
+            })
+            .unwrap_or(false)
+    }
+}
+
+pub trait Segmentation<PIT> {
+    /// split into trips of consecutive points
+    ///
+    /// expects the points to be sorted chronologically
+    fn trips(&self, options: &SegmentationOptions) -> Vec<&[PIT]>;
+}
+
+impl<PIT> Segmentation<PIT> for [PIT]
+where
+    PIT: PointInTime,
+{
+    fn trips(&self, options: &SegmentationOptions) -> Vec<&[PIT]> {
+        let mut trips = Vec::new();
+        let mut start = 0;
+        for idx in 1..self.len() {
+            if options.is_break(&self[idx - 1], &self[idx]) {
+                trips.push(&self[start..idx]);
+                start = idx;
+            }
+        }
+        if start < self.len() {
+            trips.push(&self[start..]);
+        }
+        trips
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Segmentation, SegmentationOptions};
+    use crate::algo::tests::MyPit;
+    use chrono::{DateTime, Duration, Utc};
+    use geo_types::Point;
+    use uom::si::f64::Length;
+    use uom::si::length::kilometer;
+
+    #[test]
+    fn split_trips() {
+        let points = [
+            (0, 0.0),
+            (600, 0.01),
+            (1200, 0.02),
+            (9000, 0.02),
+            (9600, 1.0),
+        ]
+        .iter()
+        .map(|(ts, x)| MyPit {
+            p: Point::new(*x, 0.0),
+            ts: DateTime::<Utc>::from_timestamp(*ts, 0).unwrap(),
+        })
+        .collect::<Vec<_>>();
+        let lens = |options: &SegmentationOptions| {
+            points
+                .trips(options)
+                .iter()
+                .map(|trip| trip.len())
+                .collect::<Vec<_>>()
+        };
+
+        let mut options = SegmentationOptions {
+            max_gap: Duration::hours(1),
+            max_distance: None,
+        };
+        assert_eq!(lens(&options), vec![3, 2]);
+        options.max_distance = Some(Length::new::<kilometer>(50.0));
+        assert_eq!(lens(&options), vec![3, 1, 1]);
+        assert!(points[..0].trips(&options).is_empty());
+    }
+}
@@ -1,35 +1,149 @@
 use geo::prelude::GeodesicDistance;
 use ordered_float::OrderedFloat;
+use std::fmt;
+use std::str::FromStr;
 use uom::si::f64::{Length, Time, Velocity};
 use uom::si::length::meter;
-use uom::si::time::second;
+use uom::si::time::millisecond;
 use uom::si::velocity::meter_per_second;
 
 use crate::algo::PointInTime;
 
-pub fn speed<CIP>(tp1: &CIP, tp2: &CIP) -> Velocity
+/// which estimate of the speed to compute when the points have an uncertain position
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SpeedBound {
+    /// the distance between the points, ignoring the uncertainty
+    #[default]
+    Nominal,
+    /// the shortest possible distance between the uncertainty circles of the points
+    Lower,
+    /// the longest possible distance between the uncertainty circles of the points
+    Upper,
+}
+
+impl FromStr for SpeedBound {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nominal" => Ok(Self::Nominal),
+            "lower" => Ok(Self::Lower),
+            "upper" => Ok(Self::Upper),
+            _ => Err(eyre::eyre!(
+                "unknown speed bound \"{}\", expected nominal, lower or upper",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for SpeedBound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Nominal => "nominal",
+            Self::Lower => "lower",
+            Self::Upper => "upper",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// the minimum time in milliseconds between two points to compute a speed from when at
+/// least one of the timestamps only has second precision, see
+/// [`PointInTime::has_exact_timestamp`]. Shorter durations - for example two tweets within
+/// the same second - are within the precision of the timestamps and would result in
+/// infinite or absurd speeds.
+pub const MIN_TIME_DELTA_MS: i64 = 1000;
+
+/// the minimum time in milliseconds between two points with millisecond precision
+/// timestamps to compute a speed from. The snowflake ids are precise, but a tweet is
+/// created some time after its location was determined.
+pub const MIN_EXACT_TIME_DELTA_MS: i64 = 100;
+
+/// the minimum time in milliseconds between the points to compute a speed from, depending
+/// on the precision of their timestamps
+pub fn min_time_delta_ms<CIP>(tp1: &CIP, tp2: &CIP) -> i64
+where
+    CIP: PointInTime,
+{
+    if tp1.has_exact_timestamp() && tp2.has_exact_timestamp() {
+        MIN_EXACT_TIME_DELTA_MS
+    } else {
+        MIN_TIME_DELTA_MS
+    }
+}
+
+pub fn speed<CIP>(tp1: &CIP, tp2: &CIP) -> Option<Velocity>
+where
+    CIP: PointInTime,
+{
+    speed_bounded(tp1, tp2, SpeedBound::Nominal)
+}
+
+/// distance between the points, taking their uncertainty into account as requested by `bound`.
+pub fn distance_bounded<CIP>(tp1: &CIP, tp2: &CIP, bound: SpeedBound) -> Length
+where
+    CIP: PointInTime,
+{
+    let distance = Length::new::<meter>(tp1.point().geodesic_distance(&tp2.point()));
+    let uncertainty = tp1.uncertainty() + tp2.uncertainty();
+    match bound {
+        SpeedBound::Nominal => distance,
+        SpeedBound::Lower => {
+            if distance > uncertainty {
+                distance - uncertainty
+            } else {
+                Length::new::<meter>(0.0)
+            }
+        }
+        SpeedBound::Upper => distance + uncertainty,
+    }
+}
+
+/// speed between the points, taking their uncertainty into account as requested by `bound`.
+///
+/// `None` when the speed is undefined because the points are closer in time than the
+/// precision of their timestamps allows, see [`min_time_delta_ms`].
+pub fn speed_bounded<CIP>(tp1: &CIP, tp2: &CIP, bound: SpeedBound) -> Option<Velocity>
 where
     CIP: PointInTime,
 {
-    let dur = tp2.timestamp() - tp1.timestamp();
-    Length::new::<meter>(tp1.point().geodesic_distance(&tp2.point()))
-        / Time::new::<second>(dur.num_seconds().abs() as f64)
+    speed_over(distance_bounded(tp1, tp2, bound), tp1, tp2)
+}
+
+/// speed to cover `distance` in the time between the points, for example along a route.
+///
+/// `None` when the speed is undefined because the points are closer in time than the
+/// precision of their timestamps allows, see [`min_time_delta_ms`].
+pub fn speed_over<CIP>(distance: Length, tp1: &CIP, tp2: &CIP) -> Option<Velocity>
+where
+    CIP: PointInTime,
+{
+    let dur_ms = (tp2.timestamp() - tp1.timestamp()).num_milliseconds().abs();
+    if dur_ms < min_time_delta_ms(tp1, tp2) {
+        return None;
+    }
+    Some(distance / Time::new::<millisecond>(dur_ms as f64))
 }
 
 pub trait Speed {
-    fn speeds(&self) -> Vec<Velocity>;
+    /// the speed of each segment between consecutive points, `None` for undefined speeds.
+    /// See [`speed_bounded`].
+    fn speeds_bounded(&self, bound: SpeedBound) -> Vec<Option<Velocity>>;
+
+    fn speeds(&self) -> Vec<Option<Velocity>> {
+        self.speeds_bounded(SpeedBound::Nominal)
+    }
 
     fn speed_max(&self) -> Option<Velocity> {
-        self.speeds()
+        self.speed_max_bounded(SpeedBound::Nominal)
+    }
+
+    fn speed_max_bounded(&self, bound: SpeedBound) -> Option<Velocity> {
+        self.speeds_bounded(bound)
             .iter()
-            .filter_map(|v| {
-                let value = v.get::<meter_per_second>();
-                if v.is_nan() {
-                    None
-                } else {
-                    Some(OrderedFloat::from(value))
-                }
-            })
+            .flatten()
+            .map(|v| OrderedFloat::from(v.get::<meter_per_second>()))
             .max()
             .map(|oflt| Velocity::new::<meter_per_second>(oflt.0))
     }
@@ -39,9 +153,101 @@ impl<CIP> Speed for [CIP]
 where
     CIP: PointInTime,
 {
-    fn speeds(&self) -> Vec<Velocity> {
+    fn speeds_bounded(&self, bound: SpeedBound) -> Vec<Option<Velocity>> {
         self.windows(2)
-            .map(|window| speed(&window[0], &window[1]))
+            .map(|window| speed_bounded(&window[0], &window[1], bound))
             .collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Speed, SpeedBound};
+    use crate::algo::PointInTime;
+    use chrono::{DateTime, Utc};
+    use geo_types::Point;
+    use uom::si::f64::Length;
+    use uom::si::length::meter;
+    use uom::si::velocity::meter_per_second;
+
+    struct UncertainPit {
+        p: Point<f64>,
+        ts: DateTime<Utc>,
+        uncertainty_m: f64,
+    }
+
+    impl PointInTime for UncertainPit {
+        fn timestamp(&self) -> DateTime<Utc> {
+            self.ts
+        }
+
+        fn point(&self) -> Point<f64> {
+            self.p
+        }
+
+        fn uncertainty(&self) -> Length {
+            Length::new::<meter>(self.uncertainty_m)
+        }
+    }
+
+    #[test]
+    fn speed_bounds() {
+        let points = [
+            UncertainPit {
+                p: Point::new(0.0, 0.0),
+                ts: DateTime::<Utc>::from_timestamp(0, 0).unwrap(),
+                uncertainty_m: 0.0,
+            },
+            UncertainPit {
+                // ~1113 m
+                p: Point::new(0.01, 0.0),
+                ts: DateTime::<Utc>::from_timestamp(100, 0).unwrap(),
+                uncertainty_m: 500.0,
+            },
+            UncertainPit {
+                p: Point::new(0.01, 0.0),
+                ts: DateTime::<Utc>::from_timestamp(200, 0).unwrap(),
+                uncertainty_m: 2000.0,
+            },
+        ];
+        let ms = |bound| {
+            points
+                .speeds_bounded(bound)
+                .iter()
+                .map(|v| v.map(|v| v.get::<meter_per_second>().round()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ms(SpeedBound::Nominal), vec![Some(11.0), Some(0.0)]);
+        assert_eq!(ms(SpeedBound::Lower), vec![Some(6.0), Some(0.0)]);
+        assert_eq!(ms(SpeedBound::Upper), vec![Some(16.0), Some(25.0)]);
+    }
+
+    #[test]
+    fn undefined_speeds() {
+        let pit = |x: f64, ts_ms: i64| UncertainPit {
+            p: Point::new(x, 0.0),
+            ts: DateTime::<Utc>::from_timestamp_millis(ts_ms).unwrap(),
+            uncertainty_m: 0.0,
+        };
+        let points = [
+            pit(0.0, 0),
+            pit(0.01, 0),
+            pit(0.01, 500),
+            pit(0.02, 100_500),
+        ];
+        let speeds = points.speeds();
+        assert!(speeds[0].is_none());
+        assert!(speeds[1].is_none());
+        assert_eq!(
+            speeds[2].map(|v| v.get::<meter_per_second>().round()),
+            Some(11.0)
+        );
+        assert_eq!(
+            points
+                .speed_max()
+                .map(|v| v.get::<meter_per_second>().round()),
+            Some(11.0)
+        );
+        assert!(points[..3].speed_max().is_none());
+    }
+}
@@ -0,0 +1,225 @@
+//! Detection of stay points and significant places
+//!
+//! A stay point is a sequence of consecutive points within a radius around its first point,
+//! spanning at least a minimum duration. Stay points close to each other are aggregated into
+//! the significant places of a user.
+
+use crate::algo::PointInTime;
+use chrono::{DateTime, Duration, Utc};
+use geo::algorithm::geodesic_distance::GeodesicDistance;
+use geo_types::Point;
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use uom::si::f64::Length;
+use uom::si::length::meter;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StayPointOptions {
+    /// the maximum distance of the points of a stay point from its first point
+    pub radius: Length,
+    /// the minimum time between the first and the last point of a stay point
+    pub min_duration: Duration,
+    /// the maximum distance of stay points from the center of their significant place
+    pub place_radius: Length,
+}
+
+impl Default for StayPointOptions {
+    fn default() -> Self {
+        Self {
+            radius: Length::new::<meter>(200.0),
+            min_duration: Duration::minutes(20),
+            place_radius: Length::new::<meter>(300.0),
+        }
+    }
+}
+
+impl Serialize for StayPointOptions {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("StayPointOptions", 3)?;
+        state.serialize_field("radius_m", &self.radius.get::<meter>())?;
+        state.serialize_field("min_duration_s", &self.min_duration.num_seconds())?;
+        state.serialize_field("place_radius_m", &self.place_radius.get::<meter>())?;
+        state.end()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StayPoint {
+    /// the centroid of the points
+    pub point: Point<f64>,
+    pub arrival: DateTime<Utc>,
+    pub departure: DateTime<Utc>,
+    /// the number of points the stay point consists of
+    pub point_count: usize,
+}
+
+impl StayPoint {
+    pub fn duration(&self) -> Duration {
+        self.departure - self.arrival
+    }
+}
+
+/// stay points close to each other, ordered by arrival
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignificantPlace {
+    /// the centroid of the stay points
+    pub point: Point<f64>,
+    pub stay_points: Vec<StayPoint>,
+}
+
+impl SignificantPlace {
+    pub fn visit_count(&self) -> usize {
+        self.stay_points.len()
+    }
+
+    /// the summed duration of all visits
+    pub fn dwell_time(&self) -> Duration {
+        self.stay_points
+            .iter()
+            .fold(Duration::zero(), |acc, sp| acc + sp.duration())
+    }
+
+    /// the number of points supporting the place
+    pub fn point_count(&self) -> usize {
+        self.stay_points.iter().map(|sp| sp.point_count).sum()
+    }
+
+    fn add(&mut self, stay_point: StayPoint) {
+        let n = self.stay_points.len() as f64;
+        self.point = Point::new(
+            (self.point.x() * n + stay_point.point.x()) / (n + 1.0),
+            (self.point.y() * n + stay_point.point.y()) / (n + 1.0),
+        );
+        self.stay_points.push(stay_point);
+    }
+}
+
+pub trait StayPoints {
+    /// expects the points to be sorted chronologically
+    fn stay_points(&self, options: &StayPointOptions) -> Vec<StayPoint>;
+
+    /// the stay points aggregated to places, ordered by descending dwell time
+    ///
+    /// expects the points to be sorted chronologically
+    fn significant_places(&self, options: &StayPointOptions) -> Vec<SignificantPlace> {
+        significant_places(self.stay_points(options), options)
+    }
+}
+
+impl<PIT> StayPoints for [PIT]
+where
+    PIT: PointInTime,
+{
+    fn stay_points(&self, options: &StayPointOptions) -> Vec<StayPoint> {
+        let radius_m = options.radius.get::<meter>();
+        let mut stay_points = Vec::new();
+        let mut i = 0;
+        while i < self.len() {
+            let anchor = self[i].point();
+            let mut j = i + 1;
+            while j < self.len() && anchor.geodesic_distance(&self[j].point()) <= radius_m {
+                j += 1;
+            }
+            let (arrival, departure) = (self[i].timestamp(), self[j - 1].timestamp());
+            if j - i >= 2 && departure - arrival >= options.min_duration {
+                let n = (j - i) as f64;
+                let (sum_x, sum_y) = self[i..j].iter().fold((0.0, 0.0), |(x, y), pit| {
+                    let p = pit.point();
+                    (x + p.x(), y + p.y())
+                });
+                stay_points.push(StayPoint {
+                    point: Point::new(sum_x / n, sum_y / n),
+                    arrival,
+                    departure,
+                    point_count: j - i,
+                });
+                i = j;
+            } else {
+                i += 1;
+            }
+        }
+        stay_points
+    }
+}
+
+/// aggregate the stay points into places. Each stay point is assigned to the first place
+/// within `place_radius`, otherwise it starts a new place.
+pub fn significant_places(
+    stay_points: Vec<StayPoint>,
+    options: &StayPointOptions,
+) -> Vec<SignificantPlace> {
+    let place_radius_m = options.place_radius.get::<meter>();
+    let mut places: Vec<SignificantPlace> = Vec::new();
+    for stay_point in stay_points {
+        match places
+            .iter_mut()
+            .find(|place| place.point.geodesic_distance(&stay_point.point) <= place_radius_m)
+        {
+            Some(place) => place.add(stay_point),
+            None => places.push(SignificantPlace {
+                point: stay_point.point,
+                stay_points: vec![stay_point],
+            }),
+        }
+    }
+    places.sort_by_key(|place| std::cmp::Reverse(place.dwell_time()));
+    places
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StayPointOptions, StayPoints};
+    use crate::algo::tests::MyPit;
+    use chrono::{DateTime, Duration, Utc};
+    use geo_types::Point;
+
+    #[test]
+    fn stay_points_and_places() {
+        // 0.001 degrees are about 111 m at the equator
+        let points = [
+            // home
+            (0, 0.0),
+            (1800, 0.001),
+            // passing by
+            (2400, 0.05),
+            // work
+            (4000, 0.1),
+            (8000, 0.1005),
+            (12000, 0.1),
+            // too short
+            (20000, 0.2),
+            (20600, 0.2),
+            // home again
+            (30000, 0.0005),
+            (40000, 0.0),
+        ]
+        .iter()
+        .map(|(ts, x)| MyPit {
+            p: Point::new(*x, 0.0),
+            ts: DateTime::<Utc>::from_timestamp(*ts, 0).unwrap(),
+        })
+        .collect::<Vec<_>>();
+        let options = StayPointOptions::default();
+
+        let stay_points = points.stay_points(&options);
+        assert_eq!(
+            stay_points
+                .iter()
+                .map(|sp| (sp.point_count, sp.duration().num_seconds()))
+                .collect::<Vec<_>>(),
+            vec![(2, 1800), (3, 8000), (2, 10000)]
+        );
+        assert!((stay_points[0].point.x() - 0.0005).abs() < 1e-9);
+
+        let places = points.significant_places(&options);
+        assert_eq!(places.len(), 2);
+        assert_eq!(places[0].visit_count(), 2);
+        assert_eq!(places[0].dwell_time(), Duration::seconds(11800));
+        assert_eq!(places[0].point_count(), 4);
+        assert_eq!(places[1].visit_count(), 1);
+        assert!((places[1].point.x() - 0.1).abs() < 0.001);
+    }
+}
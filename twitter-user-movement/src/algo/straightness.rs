@@ -1,6 +1,6 @@
 use geo::convex_hull::quick_hull;
 use geo::geodesic_distance::GeodesicDistance;
-use geo_types::{Coordinate, Point};
+use geo_types::{Coord, Point};
 use statrs::statistics::{Data, Median};
 
 /// describes the straightness of line using a single number
@@ -21,7 +21,7 @@ pub trait StraightnessChunked {
     }
 }
 
-impl Straightness for [Coordinate<f64>] {
+impl Straightness for [Coord<f64>] {
     fn straightness(&self) -> f64 {
         let s = straightness(self);
         if s.is_nan() {
@@ -32,7 +32,7 @@ impl Straightness for [Coordinate<f64>] {
     }
 }
 
-impl StraightnessChunked for [Coordinate<f64>] {
+impl StraightnessChunked for [Coord<f64>] {
     fn straightness_chunked(&self, chunk_size: usize) -> Vec<f64> {
         self.chunks(chunk_size)
             .map(|chunk| chunk.straightness())
@@ -40,14 +40,14 @@ impl StraightnessChunked for [Coordinate<f64>] {
     }
 }
 
-fn straightness(coords: &[Coordinate<f64>]) -> f64 {
+fn straightness(coords: &[Coord<f64>]) -> f64 {
     let mut coords_copy = coords.to_vec();
     geodesic_distance_covered(&quick_hull(&mut coords_copy).0)
         / 2.0
         / geodesic_distance_covered(coords)
 }
 
-fn geodesic_distance_covered(coords: &[Coordinate<f64>]) -> f64 {
+fn geodesic_distance_covered(coords: &[Coord<f64>]) -> f64 {
     coords
         .windows(2)
         .map(|window| Point::from(window[0]).geodesic_distance(&Point::from(window[1])))
@@ -9,44 +9,27 @@ where
     PIT: PointInTime,
 {
     fn sort_chronologically(&mut self) {
-        self.sort_unstable_by(|pit1, pit2| pit1.timestamp().cmp(&pit2.timestamp()))
+        self.sort_unstable_by_key(|pit| pit.timestamp())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::SortChronologically;
-    use crate::algo::PointInTime;
-    use chrono::{DateTime, NaiveDateTime, Utc};
-    use geo_types::Point;
-
-    #[derive(Clone, PartialEq, Debug)]
-    struct MyPit {
-        p: Point<f64>,
-        ts: DateTime<Utc>,
-    }
-
-    impl PointInTime for MyPit {
-        fn timestamp(&self) -> DateTime<Utc> {
-            self.ts
-        }
-
-        fn point(&self) -> Point<f64> {
-            self.p
-        }
-    }
+    use crate::algo::tests::MyPit;
+    use chrono::{DateTime, Utc};
 
     #[test]
     fn trajectory_point_sort_chronological() {
         let p1 = MyPit {
             p: Default::default(),
-            ts: DateTime::<Utc>::from_utc(NaiveDateTime::from_timestamp(10, 0), Utc),
+            ts: DateTime::<Utc>::from_timestamp(10, 0).unwrap(),
         };
         let p2 = MyPit {
             p: Default::default(),
-            ts: DateTime::<Utc>::from_utc(NaiveDateTime::from_timestamp(20, 0), Utc),
+            ts: DateTime::<Utc>::from_timestamp(20, 0).unwrap(),
         };
-        let mut v = vec![p2.clone(), p1.clone()];
+        let mut v = [p2.clone(), p1.clone()];
         v.sort_chronologically();
         assert_eq!(v[0], p1);
         assert_eq!(v[1], p2);
@@ -0,0 +1,235 @@
+//! Classification of the transport mode of the segments between consecutive points
+//!
+//! Each mode has a range of typical speeds with fuzzy borders. The directness of the movement
+//! around a segment - its straightness and the turning angles at its points - shifts the
+//! estimate between the modes bound to roads and the more direct train and flight.
+
+use crate::algo::angle::angle_radians;
+use crate::algo::speed::SpeedBound;
+use crate::algo::{PointInTime, Speed, Straightness};
+use geo_types::Coord;
+use ordered_float::OrderedFloat;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::f64::consts::PI;
+use std::fmt;
+use uom::si::f64::Velocity;
+use uom::si::velocity::kilometer_per_hour;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum TransportMode {
+    Stationary,
+    Walk,
+    Bike,
+    Car,
+    Train,
+    Flight,
+}
+
+impl TransportMode {
+    pub const ALL: [TransportMode; 6] = [
+        Self::Stationary,
+        Self::Walk,
+        Self::Bike,
+        Self::Car,
+        Self::Train,
+        Self::Flight,
+    ];
+
+    /// the speeds in km/h at which the membership in this mode starts to rise, reaches one,
+    /// starts to fall and reaches zero again
+    fn speed_range_kmh(&self) -> [f64; 4] {
+        match self {
+            Self::Stationary => [0.0, 0.0, 0.5, 2.0],
+            Self::Walk => [0.5, 2.0, 6.0, 9.0],
+            Self::Bike => [5.0, 10.0, 22.0, 30.0],
+            Self::Car => [15.0, 30.0, 120.0, 160.0],
+            Self::Train => [40.0, 80.0, 250.0, 330.0],
+            Self::Flight => [200.0, 350.0, f64::INFINITY, f64::INFINITY],
+        }
+    }
+
+    /// modes which are not bound to a road network move more directly
+    fn is_direct(&self) -> bool {
+        matches!(self, Self::Train | Self::Flight)
+    }
+
+    fn speed_membership(&self, speed_kmh: f64) -> f64 {
+        let [rise, full, fall, zero] = self.speed_range_kmh();
+        if speed_kmh < rise || speed_kmh > zero {
+            0.0
+        } else if speed_kmh < full {
+            (speed_kmh - rise) / (full - rise)
+        } else if speed_kmh <= fall {
+            1.0
+        } else {
+            (zero - speed_kmh) / (zero - fall)
+        }
+    }
+}
+
+impl fmt::Display for TransportMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Stationary => "stationary",
+            Self::Walk => "walk",
+            Self::Bike => "bike",
+            Self::Car => "car",
+            Self::Train => "train",
+            Self::Flight => "flight",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct ModeEstimate {
+    pub mode: TransportMode,
+    /// the share of the score of the mode in the scores of all modes, between 0 and 1
+    pub confidence: f64,
+}
+
+/// classify a segment by its speed and the directness of the movement around it.
+///
+/// `directness` ranges from 0 for a winding movement to 1 for a straight line.
+pub fn classify(speed: Velocity, directness: f64) -> ModeEstimate {
+    let speed_kmh = speed.get::<kilometer_per_hour>();
+    let scores = TransportMode::ALL.map(|mode| {
+        let factor = if mode.is_direct() {
+            0.5 + 0.5 * directness
+        } else {
+            1.0 - 0.25 * directness
+        };
+        (mode, mode.speed_membership(speed_kmh) * factor)
+    });
+    let total: f64 = scores.iter().map(|(_, score)| score).sum();
+    let (mode, score) = scores
+        .iter()
+        .copied()
+        .max_by_key(|(_, score)| OrderedFloat(*score))
+        .unwrap_or((TransportMode::Stationary, 0.0));
+    ModeEstimate {
+        mode,
+        confidence: if total > 0.0 { score / total } else { 0.0 },
+    }
+}
+
+/// the directness of the segment between `coords[idx]` and `coords[idx + 1]`, combining the
+/// straightness of the surrounding points with the turning angles at both ends.
+fn segment_directness(coords: &[Coord<f64>], idx: usize) -> f64 {
+    let window = &coords[idx.saturating_sub(1)..(idx + 3).min(coords.len())];
+    let straightness = window.straightness().clamp(0.0, 1.0);
+
+    let turns = window
+        .windows(3)
+        .map(|w| angle_radians(&[w[0], w[1], w[2]]))
+        .filter(|angle| !angle.is_nan())
+        .collect::<Vec<_>>();
+    let curviness = if turns.is_empty() {
+        0.0
+    } else {
+        turns.iter().sum::<f64>() / turns.len() as f64 / PI
+    };
+    straightness * (1.0 - curviness)
+}
+
+pub trait TransportModes {
+    /// the mode of each segment between consecutive points, `None` when the speed of the
+    /// segment is undefined.
+    ///
+    /// expects the points to be sorted chronologically
+    fn transport_modes(&self, bound: SpeedBound) -> Vec<Option<ModeEstimate>>;
+
+    /// the mode of each segment from speeds computed elsewhere, for example along the routes
+    /// of a road network. `speeds` holds one speed for each segment.
+    ///
+    /// expects the points to be sorted chronologically
+    fn transport_modes_from_speeds(&self, speeds: &[Option<Velocity>])
+        -> Vec<Option<ModeEstimate>>;
+}
+
+impl<PIT> TransportModes for [PIT]
+where
+    PIT: PointInTime,
+{
+    fn transport_modes(&self, bound: SpeedBound) -> Vec<Option<ModeEstimate>> {
+        self.transport_modes_from_speeds(&self.speeds_bounded(bound))
+    }
+
+    fn transport_modes_from_speeds(
+        &self,
+        speeds: &[Option<Velocity>],
+    ) -> Vec<Option<ModeEstimate>> {
+        let coords = self.iter().map(|pit| pit.point().0).collect::<Vec<_>>();
+        speeds
+            .iter()
+            .enumerate()
+            .map(|(idx, speed)| {
+                speed.map(|speed| classify(speed, segment_directness(&coords, idx)))
+            })
+            .collect()
+    }
+}
+
+/// the share of each mode in the classified segments
+pub fn mode_shares(modes: &[Option<ModeEstimate>]) -> BTreeMap<TransportMode, f64> {
+    let classified = modes.iter().flatten().collect::<Vec<_>>();
+    let mut shares = BTreeMap::new();
+    for estimate in classified.iter() {
+        *shares.entry(estimate.mode).or_default() += 1.0 / classified.len() as f64;
+    }
+    shares
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{classify, mode_shares, TransportMode, TransportModes};
+    use crate::algo::speed::SpeedBound;
+    use crate::algo::tests::MyPit;
+    use chrono::{DateTime, Utc};
+    use geo_types::Point;
+    use uom::si::f64::Velocity;
+    use uom::si::velocity::kilometer_per_hour;
+
+    #[test]
+    fn classify_by_speed() {
+        let mode = |kmh: f64, directness: f64| {
+            classify(Velocity::new::<kilometer_per_hour>(kmh), directness).mode
+        };
+        assert_eq!(mode(0.0, 0.5), TransportMode::Stationary);
+        assert_eq!(mode(4.0, 0.5), TransportMode::Walk);
+        assert_eq!(mode(15.0, 0.5), TransportMode::Bike);
+        assert_eq!(mode(60.0, 0.5), TransportMode::Car);
+        assert_eq!(mode(800.0, 0.5), TransportMode::Flight);
+        assert_eq!(mode(5000.0, 0.5), TransportMode::Flight);
+
+        // the directness decides between car and train
+        assert_eq!(mode(100.0, 0.0), TransportMode::Car);
+        assert_eq!(mode(100.0, 1.0), TransportMode::Train);
+
+        let estimate = classify(Velocity::new::<kilometer_per_hour>(60.0), 0.5);
+        assert!(estimate.confidence > 0.5 && estimate.confidence <= 1.0);
+    }
+
+    #[test]
+    fn modes_of_segments() {
+        // ~11 km per hour along a straight line, then a duplicate timestamp
+        let points = [(0.0, 0), (0.1, 3600), (0.2, 7200), (0.2, 7200)]
+            .iter()
+            .map(|(x, ts)| MyPit {
+                p: Point::new(*x, 0.0),
+                ts: DateTime::<Utc>::from_timestamp(*ts, 0).unwrap(),
+            })
+            .collect::<Vec<_>>();
+        let modes = points.transport_modes(SpeedBound::Nominal);
+        assert_eq!(modes.len(), 3);
+        assert_eq!(modes[0].map(|e| e.mode), Some(TransportMode::Bike));
+        assert_eq!(modes[1].map(|e| e.mode), Some(TransportMode::Bike));
+        assert!(modes[2].is_none());
+
+        let shares = mode_shares(&modes);
+        assert_eq!(shares.len(), 1);
+        assert_eq!(shares[&TransportMode::Bike], 1.0);
+    }
+}
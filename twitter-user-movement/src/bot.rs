@@ -0,0 +1,151 @@
+//! heuristics to recognize automated accounts
+//!
+//! Weather stations, traffic bots and similar accounts post from fixed places at fixed
+//! intervals using the same text template. Each heuristic yields a value between 0 (human-like)
+//! and 1 (bot-like), the score of a user is the mean of these values.
+
+use crate::model::{MovementPoint, UserMovement};
+use std::collections::HashMap;
+
+/// the minimum number of points for the heuristics to be meaningful. Users with less points
+/// get a score of 0.
+const MIN_POINTS: usize = 3;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BotScore {
+    /// regularity of the intervals between the points. 1 for equal intervals, approaching 0
+    /// with increasing variation of the intervals.
+    pub interval_regularity: f64,
+    /// the share of points at the most frequent location
+    pub identical_location_ratio: f64,
+    /// the share of texts following the most frequent template. Digits are ignored as they
+    /// are typically the variable parts of the template.
+    pub text_templating: f64,
+}
+
+impl BotScore {
+    /// the combined score between 0 and 1
+    pub fn score(&self) -> f64 {
+        (self.interval_regularity + self.identical_location_ratio + self.text_templating) / 3.0
+    }
+
+    /// expects the points to be sorted chronologically
+    pub fn from_points(points: &[MovementPoint]) -> Self {
+        if points.len() < MIN_POINTS {
+            return Self::default();
+        }
+        Self {
+            interval_regularity: interval_regularity(points),
+            identical_location_ratio: most_frequent_ratio(
+                points
+                    .iter()
+                    .map(|mp| (mp.point.x().to_bits(), mp.point.y().to_bits())),
+            ),
+            text_templating: most_frequent_ratio(points.iter().map(|mp| text_template(&mp.text))),
+        }
+    }
+}
+
+impl UserMovement {
+    /// expects the point to be sorted chronologically
+    pub fn bot_score(&self) -> BotScore {
+        BotScore::from_points(&self.points)
+    }
+}
+
+/// `1 / (1 + cv)` using the coefficient of variation `cv` of the intervals
+fn interval_regularity(points: &[MovementPoint]) -> f64 {
+    let intervals: Vec<f64> = points
+        .windows(2)
+        .map(|w| (w[1].timestamp - w[0].timestamp).num_milliseconds() as f64)
+        .collect();
+    let mean = intervals.iter().sum::<f64>() / intervals.len() as f64;
+    if mean <= 0.0 {
+        return 0.0;
+    }
+    let variance =
+        intervals.iter().map(|i| (i - mean).powi(2)).sum::<f64>() / intervals.len() as f64;
+    1.0 / (1.0 + variance.sqrt() / mean)
+}
+
+fn most_frequent_ratio<K, I>(values: I) -> f64
+where
+    K: std::hash::Hash + Eq,
+    I: Iterator<Item = K>,
+{
+    let mut counts: HashMap<K, usize> = HashMap::new();
+    let mut total = 0;
+    for value in values {
+        *counts.entry(value).or_default() += 1;
+        total += 1;
+    }
+    match counts.values().max() {
+        Some(max) if total > 0 => *max as f64 / total as f64,
+        _ => 0.0,
+    }
+}
+
+/// normalize the text by replacing digit sequences and collapsing whitespace
+fn text_template(text: &str) -> String {
+    let mut template = String::with_capacity(text.len());
+    let mut last = ' ';
+    for c in text.trim().chars() {
+        let c = if c.is_ascii_digit() {
+            '#'
+        } else if c.is_whitespace() {
+            ' '
+        } else {
+            c
+        };
+        if !(c == last && (c == '#' || c == ' ')) {
+            template.push(c);
+        }
+        last = c;
+    }
+    template.to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::text_template;
+    use crate::model::UserMovement;
+
+    #[test]
+    fn score_bots() {
+        let bot = UserMovement::along_equator(&[
+            (0, 1.0, "Temperature 12.5 C, wind 3 km/h"),
+            (3600, 1.0, "Temperature 13.1 C, wind 10 km/h"),
+            (7200, 1.0, "Temperature 14.0 C,  wind 4 km/h"),
+            (10800, 1.0, "Temperature 14.2 C, wind 0 km/h"),
+        ])
+        .bot_score();
+        assert!((bot.score() - 1.0).abs() < 1e-9);
+
+        let human = UserMovement::along_equator(&[
+            (0, 1.0, "good morning"),
+            (600, 2.0, "on my way"),
+            (20000, 3.0, "finally arrived"),
+            (20100, 1.0, "home again"),
+        ])
+        .bot_score();
+        assert!((human.identical_location_ratio - 0.5).abs() < 1e-9);
+        assert!((human.text_templating - 0.25).abs() < 1e-9);
+        assert!(human.interval_regularity < 0.6);
+        assert!(human.score() < 0.5);
+
+        assert_eq!(
+            UserMovement::along_equator(&[(0, 1.0, "a"), (1, 1.0, "a")])
+                .bot_score()
+                .score(),
+            0.0
+        );
+    }
+
+    #[test]
+    fn templates() {
+        assert_eq!(
+            text_template(" Level 12.5 m\n at 10:00 "),
+            "level #.# m at #:#"
+        );
+    }
+}
@@ -0,0 +1,202 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use geo::algorithm::contains::Contains;
+use geo_types::{Geometry, MultiPolygon, Point, Rect};
+use geojson::GeoJson;
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use std::fmt;
+use std::path::Path;
+
+/// restricts the tweets stored as points of the movements
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TweetFilter {
+    /// only tweets created at or after this time
+    pub since: Option<DateTime<Utc>>,
+    /// only tweets created before this time
+    pub until: Option<DateTime<Utc>>,
+    /// only tweets located within this rectangle
+    pub bbox: Option<Rect<f64>>,
+    /// only tweets located within this area
+    pub within: Option<Area>,
+    /// only tweets in one of these languages. All languages when empty.
+    pub langs: Vec<String>,
+}
+
+/// an area of interest loaded from a GeoJSON file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Area {
+    pub source: String,
+    pub multi_polygon: MultiPolygon<f64>,
+}
+
+/// the filter rejecting a tweet
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterReason {
+    Time,
+    Bbox,
+    Within,
+    Lang,
+}
+
+impl fmt::Display for FilterReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Time => "time",
+            Self::Bbox => "bbox",
+            Self::Within => "area",
+            Self::Lang => "language",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl TweetFilter {
+    /// check the tweet against the filters. Returns the first filter rejecting the tweet.
+    pub fn check(
+        &self,
+        point: &Point<f64>,
+        timestamp: &DateTime<Utc>,
+        lang: Option<&str>,
+    ) -> Option<FilterReason> {
+        if self.since.map(|since| timestamp < &since).unwrap_or(false)
+            || self.until.map(|until| timestamp >= &until).unwrap_or(false)
+        {
+            return Some(FilterReason::Time);
+        }
+        if !self.langs.is_empty()
+            && !lang
+                .map(|l| self.langs.iter().any(|fl| fl == l))
+                .unwrap_or(false)
+        {
+            return Some(FilterReason::Lang);
+        }
+        if let Some(bbox) = self.bbox.as_ref() {
+            let (min, max) = (bbox.min(), bbox.max());
+            if point.x() < min.x || point.x() > max.x || point.y() < min.y || point.y() > max.y {
+                return Some(FilterReason::Bbox);
+            }
+        }
+        if let Some(area) = self.within.as_ref() {
+            if !area.multi_polygon.contains(point) {
+                return Some(FilterReason::Within);
+            }
+        }
+        None
+    }
+}
+
+impl Serialize for TweetFilter {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("TweetFilter", 5)?;
+        state.serialize_field("since", &self.since)?;
+        state.serialize_field("until", &self.until)?;
+        state.serialize_field(
+            "bbox",
+            &self
+                .bbox
+                .map(|r| [r.min().x, r.min().y, r.max().x, r.max().y]),
+        )?;
+        state.serialize_field("within", &self.within.as_ref().map(|a| &a.source))?;
+        state.serialize_field("langs", &self.langs)?;
+        state.end()
+    }
+}
+
+impl Area {
+    /// load all polygons contained in a GeoJSON file
+    pub fn from_geojson_file<P: AsRef<Path>>(path: P) -> eyre::Result<Self> {
+        let geojson: GeoJson = std::fs::read_to_string(path.as_ref())?.parse()?;
+        let collection = geojson::quick_collection(&geojson)?;
+        let mut polygons = Vec::new();
+        for geometry in collection {
+            match geometry {
+                Geometry::Polygon(polygon) => polygons.push(polygon),
+                Geometry::MultiPolygon(mp) => polygons.extend(mp.0),
+                _ => (),
+            }
+        }
+        if polygons.is_empty() {
+            return Err(eyre::eyre!(
+                "{} does not contain any polygons",
+                path.as_ref().display()
+            ));
+        }
+        Ok(Self {
+            source: path.as_ref().display().to_string(),
+            multi_polygon: MultiPolygon(polygons),
+        })
+    }
+}
+
+/// parse a timestamp given as RFC 3339 or as a date
+pub fn parse_datetime(s: &str) -> eyre::Result<DateTime<Utc>> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+        return Ok(dt.with_timezone(&Utc));
+    }
+    let date = NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .map_err(|e| eyre::eyre!("invalid timestamp or date \"{}\": {}", s, e))?;
+    Ok(date
+        .and_hms_opt(0, 0, 0)
+        .ok_or_else(|| eyre::eyre!("invalid date {}", s))?
+        .and_utc())
+}
+
+/// parse a bbox given as `min_x,min_y,max_x,max_y`
+pub fn parse_bbox(s: &str) -> eyre::Result<Rect<f64>> {
+    let values = s
+        .split(',')
+        .map(|v| v.trim().parse::<f64>())
+        .collect::<Result<Vec<_>, _>>()?;
+    match values[..] {
+        [min_x, min_y, max_x, max_y] => Ok(Rect::new((min_x, min_y), (max_x, max_y))),
+        _ => Err(eyre::eyre!(
+            "expected the bbox as min_x,min_y,max_x,max_y, found \"{}\"",
+            s
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_bbox, parse_datetime, Area, FilterReason, TweetFilter};
+    use geo_types::{MultiPolygon, Point, Rect};
+
+    #[test]
+    fn check_filters() {
+        let filter = TweetFilter {
+            since: Some(parse_datetime("2020-09-01").unwrap()),
+            until: Some(parse_datetime("2020-10-01T00:00:00Z").unwrap()),
+            bbox: Some(parse_bbox("0,0,10,10").unwrap()),
+            within: Some(Area {
+                source: "test".to_string(),
+                multi_polygon: MultiPolygon(vec![Rect::new((0.0, 0.0), (5.0, 5.0)).to_polygon()]),
+            }),
+            langs: vec!["en".to_string(), "de".to_string()],
+        };
+        let ts = parse_datetime("2020-09-18").unwrap();
+        let inside = Point::new(1.0, 1.0);
+        assert_eq!(filter.check(&inside, &ts, Some("de")), None);
+        assert_eq!(
+            filter.check(&inside, &parse_datetime("2020-10-01").unwrap(), Some("de")),
+            Some(FilterReason::Time)
+        );
+        assert_eq!(
+            filter.check(&inside, &ts, Some("fr")),
+            Some(FilterReason::Lang)
+        );
+        assert_eq!(filter.check(&inside, &ts, None), Some(FilterReason::Lang));
+        assert_eq!(
+            filter.check(&Point::new(11.0, 1.0), &ts, Some("en")),
+            Some(FilterReason::Bbox)
+        );
+        assert_eq!(
+            filter.check(&Point::new(7.0, 1.0), &ts, Some("en")),
+            Some(FilterReason::Within)
+        );
+        assert_eq!(TweetFilter::default().check(&inside, &ts, None), None);
+    }
+}
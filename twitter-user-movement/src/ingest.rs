@@ -0,0 +1,1103 @@
+use crate::algo::{RemoveOutliers, SortChronologically};
+use crate::filter::{FilterReason, TweetFilter};
+use crate::input::Input;
+use crate::model::{MovementPoint, PlaceInfo, UserMovement};
+use crate::report::{ErrorKind, IngestReport, InputReport};
+use crate::select::UserSelection;
+use crate::spill::{BucketWriter, SpillOptions};
+use crate::tweet::{parse_line, PlaceType, Tweet};
+use rayon::prelude::*;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufWriter, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use uom::si::f64::Velocity;
+
+pub type Movements = HashMap<u64, UserMovement>;
+
+/// number of lines deserialized as one unit of work by default
+pub const DEFAULT_CHUNK_LINES: usize = 10_000;
+
+/// how to treat retweets and quoted tweets
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RetweetPolicy {
+    /// ignore retweets. Quoting tweets are kept as they are written by the user.
+    Skip,
+    /// treat retweets as observations of the retweeting user
+    #[default]
+    Keep,
+    /// replace retweets by the embedded original tweet, observing its author and location.
+    /// Quoted tweets are added as additional observations of their authors.
+    Original,
+}
+
+impl FromStr for RetweetPolicy {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "skip" => Ok(Self::Skip),
+            "keep" => Ok(Self::Keep),
+            "original" => Ok(Self::Original),
+            _ => Err(eyre::eyre!(
+                "unknown retweet policy \"{}\", expected skip, keep or original",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for RetweetPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Skip => "skip",
+            Self::Keep => "keep",
+            Self::Original => "original",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl RetweetPolicy {
+    /// the tweets to add as points for the given tweet, together with their `is_retweet` flag
+    fn observations(&self, mut tweet: Tweet) -> Vec<(Tweet, bool)> {
+        let retweeted = tweet.retweeted_status.take();
+        let quoted = tweet.quoted_status.take();
+        match (self, retweeted) {
+            (Self::Skip, Some(_)) => vec![],
+            (Self::Keep, Some(_)) => vec![(tweet, true)],
+            (Self::Original, Some(original)) => vec![(*original, false)],
+            (_, None) => {
+                let mut observations = vec![(tweet, false)];
+                if let (Self::Original, Some(quoted)) = (self, quoted) {
+                    observations.push((*quoted, false));
+                }
+                observations
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IngestOptions {
+    pub retweet_policy: RetweetPolicy,
+
+    /// drop tweets which are only located by a place coarser than this. Country centroids
+    /// produce absurd speeds. Places without a type are kept.
+    pub max_place_type: Option<PlaceType>,
+
+    pub filter: TweetFilter,
+
+    /// remove points which can only be reached with a higher speed, see [`RemoveOutliers`]
+    pub max_speed: Option<Velocity>,
+
+    /// the users to keep after all inputs have been read
+    pub selection: UserSelection,
+
+    /// abort the ingestion when more than this number of lines have been rejected
+    pub max_errors: Option<u64>,
+
+    /// write rejected lines unchanged to this file
+    pub quarantine: Option<PathBuf>,
+
+    /// the number of lines of an input deserialized as one unit of work
+    pub chunk_lines: usize,
+}
+
+impl Default for IngestOptions {
+    fn default() -> Self {
+        Self {
+            retweet_policy: Default::default(),
+            max_place_type: None,
+            filter: Default::default(),
+            max_speed: None,
+            selection: Default::default(),
+            max_errors: None,
+            quarantine: None,
+            chunk_lines: DEFAULT_CHUNK_LINES,
+        }
+    }
+}
+
+impl IngestOptions {
+    /// the options to parse new tweets before merging them with [`update_movements`]. The
+    /// outlier removal and the selection only apply to the merged movements, which provide
+    /// the context of the existing points.
+    pub fn for_update(&self) -> Self {
+        Self {
+            max_speed: None,
+            selection: UserSelection {
+                min_points: 0,
+                ..Default::default()
+            },
+            ..self.clone()
+        }
+    }
+}
+
+/// state shared between the threads parsing the inputs
+struct Ingestion<'a> {
+    options: &'a IngestOptions,
+    error_count: AtomicU64,
+    quarantine: Option<Mutex<BufWriter<File>>>,
+}
+
+impl<'a> Ingestion<'a> {
+    fn new(options: &'a IngestOptions) -> eyre::Result<Self> {
+        let quarantine = match options.quarantine.as_ref() {
+            Some(path) => Some(Mutex::new(BufWriter::new(File::create(path)?))),
+            None => None,
+        };
+        Ok(Self {
+            options,
+            error_count: AtomicU64::new(0),
+            quarantine,
+        })
+    }
+
+    fn add_errors(&self, input: &Input, n: u64) -> eyre::Result<()> {
+        let error_count = self.error_count.fetch_add(n, Ordering::SeqCst) + n;
+        if let Some(max_errors) = self.options.max_errors {
+            if error_count > max_errors {
+                return Err(eyre::eyre!(
+                    "exceeded the maximum of {} rejected lines while reading {}",
+                    max_errors,
+                    input
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn quarantine_lines<'l, I>(&self, lines: I) -> eyre::Result<()>
+    where
+        I: Iterator<Item = &'l [u8]>,
+    {
+        if let Some(quarantine) = self.quarantine.as_ref() {
+            let mut writer = quarantine
+                .lock()
+                .map_err(|_| eyre::eyre!("quarantine writer is poisoned"))?;
+            for line in lines {
+                writer.write_all(line)?;
+                if !line.ends_with(b"\n") {
+                    writer.write_all(b"\n")?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> eyre::Result<()> {
+        if let Some(quarantine) = self.quarantine {
+            quarantine
+                .into_inner()
+                .map_err(|_| eyre::eyre!("quarantine writer is poisoned"))?
+                .flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// collects the movements of users from tweets obtained elsewhere, for example from
+/// a stream.
+///
+/// The tweets are treated the same way as the tweets read by [`parse_movements`].
+pub struct MovementsBuilder {
+    options: IngestOptions,
+    movements: Movements,
+    report: InputReport,
+}
+
+impl MovementsBuilder {
+    pub fn new(options: IngestOptions) -> Self {
+        Self {
+            options,
+            movements: Movements::new(),
+            report: InputReport::new("tweets".to_string()),
+        }
+    }
+
+    /// add a tweet. Fails when the location of the tweet or of one of its observations is
+    /// invalid, the observations with a valid location are added anyway.
+    pub fn add_tweet(&mut self, tweet: Tweet) -> eyre::Result<()> {
+        add_tweet(&mut self.movements, &mut self.report, tweet, &self.options)
+    }
+
+    /// deduplicate the points, sort them by time and select the users.
+    pub fn build(self) -> (Movements, IngestReport) {
+        let mut movements = self.movements;
+        let mut report = IngestReport::new(&self.options);
+        report.add_input(self.report);
+        finish_movements(
+            &mut movements,
+            self.options.max_speed,
+            &self.options.selection,
+            &mut report,
+        );
+        (movements, report)
+    }
+}
+
+/// parse the movements of all users from the given inputs.
+///
+/// The inputs are parsed in parallel, and the lines of each input are deserialized in
+/// parallel chunks. The results are merged in input order, so the output is the same as
+/// when reading the inputs one after another.
+///
+/// Lines which can not be used are not fatal, they are counted in the returned report.
+pub fn parse_movements(
+    inputs: &[Input],
+    options: &IngestOptions,
+) -> eyre::Result<(Movements, IngestReport)> {
+    let ingestion = Ingestion::new(options)?;
+    let per_input = inputs
+        .par_iter()
+        .map(|input| {
+            let mut movements = Movements::new();
+            let report = parse_input(input, &ingestion, &mut |chunk_movements| {
+                merge_movements(&mut movements, chunk_movements);
+                Ok(())
+            })?;
+            Ok((movements, report))
+        })
+        .collect::<eyre::Result<Vec<_>>>()?;
+    ingestion.finish()?;
+
+    let mut movements = Movements::new();
+    let mut report = IngestReport::new(options);
+    for (input_movements, input_report) in per_input {
+        merge_movements(&mut movements, input_movements);
+        report.add_input(input_report);
+    }
+    finish_movements(
+        &mut movements,
+        options.max_speed,
+        &options.selection,
+        &mut report,
+    );
+    Ok((movements, report))
+}
+
+/// parse the movements of all users from the given inputs with bounded memory usage.
+///
+/// The users are partitioned into buckets on disk, which are processed one after another
+/// by passing their movements to `on_movements`. The inputs are read one after another to
+/// keep the order of the points the same as in [`parse_movements`]. When the memory budget
+/// needs more than [`MAX_BUCKETS`](crate::spill::MAX_BUCKETS) buckets, the inputs are read
+/// several times, each pass keeping the users of its own buckets.
+pub fn parse_movements_spilled<F>(
+    inputs: &[Input],
+    options: &IngestOptions,
+    spill_options: &SpillOptions,
+    mut on_movements: F,
+) -> eyre::Result<IngestReport>
+where
+    F: FnMut(Movements) -> eyre::Result<()>,
+{
+    let plan = spill_options.bucket_plan(inputs)?;
+    // the later passes read the same lines, their rejects have been handled by the first one
+    let later_pass_options = IngestOptions {
+        max_errors: None,
+        quarantine: None,
+        ..options.clone()
+    };
+    let mut report = IngestReport::new(options);
+    for pass in 0..plan.passes {
+        let ingestion = Ingestion::new(if pass == 0 {
+            options
+        } else {
+            &later_pass_options
+        })?;
+        let mut bucket_writer = BucketWriter::new(spill_options, plan, pass)?;
+        for input in inputs {
+            let input_report = parse_input(input, &ingestion, &mut |chunk_movements| {
+                bucket_writer.push(chunk_movements)
+            })?;
+            if pass == 0 {
+                report.add_input(input_report);
+            }
+        }
+        ingestion.finish()?;
+
+        let buckets = bucket_writer.finish()?;
+        for bucket in 0..buckets.count() {
+            let mut movements = buckets.read(bucket)?;
+            finish_movements(
+                &mut movements,
+                options.max_speed,
+                &options.selection,
+                &mut report,
+            );
+            on_movements(movements)?;
+        }
+    }
+    Ok(report)
+}
+
+/// merge newly parsed movements into previously computed movements.
+///
+/// Only the users with new points are touched: their points are deduplicated and sorted
+/// again, their outliers are removed and they are checked against the selection again. All
+/// other users are kept unchanged. The travel speeds and transport modes are recomputed by the
+/// [`MovementJsonWriter`](crate::output::MovementJsonWriter).
+pub fn update_movements(
+    existing: &mut Movements,
+    new: Movements,
+    max_speed: Option<Velocity>,
+    selection: &UserSelection,
+    report: &mut IngestReport,
+) {
+    let mut touched = Movements::with_capacity(new.len());
+    for (user_id, user_movement) in new {
+        let user_movement = match existing.remove(&user_id) {
+            Some(mut previous) => {
+                previous.points.extend(user_movement.points);
+                previous.outliers_removed += user_movement.outliers_removed;
+                previous
+            }
+            None => user_movement,
+        };
+        touched.insert(user_id, user_movement);
+    }
+    finish_movements(&mut touched, max_speed, selection, report);
+
+    report.users_updated = touched.len() as u64;
+    existing.extend(touched);
+    report.users = existing.len() as u64;
+}
+
+/// deduplicate, sort the points by time, remove the outliers and drop the users not matching
+/// the selection.
+///
+/// The dropped duplicates, outliers and users are counted in the report.
+fn finish_movements(
+    movements: &mut Movements,
+    max_speed: Option<Velocity>,
+    selection: &UserSelection,
+    report: &mut IngestReport,
+) {
+    report.duplicates_dropped += movements
+        .par_iter_mut()
+        .map(|(_, v)| deduplicate(&mut v.points))
+        .sum::<u64>();
+
+    // sort by time
+    movements.par_iter_mut().for_each(|(_, v)| {
+        v.points.sort_chronologically();
+    });
+
+    if let Some(max_speed) = max_speed {
+        report.outliers_removed += movements
+            .par_iter_mut()
+            .map(|(_, v)| {
+                let removed = v.points.remove_outliers(max_speed);
+                v.outliers_removed += removed;
+                removed as u64
+            })
+            .sum::<u64>();
+    }
+
+    let rejected = movements
+        .par_iter()
+        .filter_map(|(user_id, v)| selection.check(v).map(|rule| (*user_id, rule)))
+        .collect::<Vec<_>>();
+    for (user_id, rule) in rejected {
+        movements.remove(&user_id);
+        *report.users_dropped.entry(rule).or_default() += 1;
+    }
+    report.users += movements.len() as u64;
+}
+
+/// parse an input, passing the movements of each chunk to `sink` in input order.
+fn parse_input(
+    input: &Input,
+    ingestion: &Ingestion,
+    sink: &mut dyn FnMut(Movements) -> eyre::Result<()>,
+) -> eyre::Result<InputReport> {
+    let mut bufreader = input.open()?;
+    let mut report = InputReport::new(input.to_string());
+
+    // read as many chunks as there are threads before handing them to rayon to
+    // keep the memory usage bounded.
+    let chunks_per_batch = rayon::current_num_threads().max(1);
+    let mut lines_read = 0_u64;
+    loop {
+        let mut chunks = Vec::with_capacity(chunks_per_batch);
+        let mut eof = false;
+        while chunks.len() < chunks_per_batch && !eof {
+            let first_line = lines_read + 1;
+            let chunk_lines = ingestion.options.chunk_lines.max(1);
+            let mut chunk = Vec::with_capacity(chunk_lines);
+            while chunk.len() < chunk_lines {
+                let mut line = Vec::new();
+                if bufreader.read_until(b'\n', &mut line)? == 0 {
+                    eof = true;
+                    break;
+                }
+                chunk.push(line);
+            }
+            lines_read += chunk.len() as u64;
+            if !chunk.is_empty() {
+                chunks.push((first_line, chunk));
+            }
+        }
+
+        let chunk_results = chunks
+            .par_iter()
+            .map(|(first_line, chunk)| parse_lines(chunk, *first_line, ingestion.options))
+            .collect::<Vec<_>>();
+
+        for ((first_line, chunk), (chunk_movements, chunk_report)) in
+            chunks.iter().zip(chunk_results)
+        {
+            ingestion.quarantine_lines(
+                chunk_report
+                    .rejected_lines
+                    .iter()
+                    .map(|rl| chunk[(rl.line - first_line) as usize].as_slice()),
+            )?;
+            ingestion.add_errors(input, chunk_report.error_count())?;
+            sink(chunk_movements)?;
+            report.merge(chunk_report);
+        }
+
+        if eof {
+            break;
+        }
+    }
+    Ok(report)
+}
+
+/// parse the lines of a chunk. `first_line` is the line number of the first line of
+/// the chunk within its input.
+fn parse_lines(
+    lines: &[Vec<u8>],
+    first_line: u64,
+    options: &IngestOptions,
+) -> (Movements, InputReport) {
+    let mut movements = Movements::new();
+    let mut report = InputReport::default();
+    for (line, line_number) in lines.iter().zip(first_line..) {
+        report.lines += 1;
+        if line.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+        let tweets = match parse_line(line) {
+            Ok(tweets) => tweets,
+            Err(e) => {
+                report.reject(line_number, ErrorKind::from_json_error(&e), e.to_string());
+                continue;
+            }
+        };
+        // a line of several tweets is rejected once, the valid tweets are kept
+        let errors = tweets
+            .into_iter()
+            .filter_map(|tweet| add_tweet(&mut movements, &mut report, tweet, options).err())
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>();
+        if !errors.is_empty() {
+            report.reject(line_number, ErrorKind::InvalidGeometry, errors.join("; "));
+        }
+    }
+    (movements, report)
+}
+
+/// add the observations of a tweet according to the options, counting it in the report.
+///
+/// Fails with the first error when the location of an observation is invalid. The other
+/// observations are added anyway.
+fn add_tweet(
+    movements: &mut Movements,
+    report: &mut InputReport,
+    tweet: Tweet,
+    options: &IngestOptions,
+) -> eyre::Result<()> {
+    report.tweets += 1;
+    if tweet.is_retweet() {
+        report.retweets += 1;
+    }
+    let mut error = None;
+    for (tweet, is_retweet) in options.retweet_policy.observations(tweet) {
+        if let (Some(max_place_type), Some(place_type)) =
+            (options.max_place_type, tweet.inexact_place_type())
+        {
+            // the coarseness of untyped places is unknown
+            if place_type == PlaceType::Unknown {
+                report.untyped_places_kept += 1;
+            } else if place_type > max_place_type {
+                report.coarse_places_dropped += 1;
+                continue;
+            }
+        }
+        match add_point(movements, tweet, is_retweet, &options.filter) {
+            Ok(Added::Point) => report.tweets_with_location += 1,
+            Ok(Added::Filtered(reason)) => {
+                report.tweets_with_location += 1;
+                *report.filtered.entry(reason).or_default() += 1;
+            }
+            Ok(Added::NoLocation) => (),
+            Err(e) => {
+                error.get_or_insert(e);
+            }
+        }
+    }
+    error.map_or(Ok(()), Err)
+}
+
+enum Added {
+    Point,
+    NoLocation,
+    Filtered(FilterReason),
+}
+
+/// add the tweet to the movements when it has a location and passes the filter
+fn add_point(
+    movements: &mut Movements,
+    tweet: Tweet,
+    is_retweet: bool,
+    filter: &TweetFilter,
+) -> eyre::Result<Added> {
+    if let Some((point, is_exact_location)) = tweet.geo_point()? {
+        let timestamp = tweet.timestamp();
+        if let Some(reason) = filter.check(&point, &timestamp, tweet.lang.as_deref()) {
+            return Ok(Added::Filtered(reason));
+        }
+        let movement_point = MovementPoint {
+            tweet_id: tweet.id,
+            point,
+            is_exact_location,
+            uncertainty_m: tweet.uncertainty_radius_m(&point)?,
+            is_retweet,
+            timestamp,
+            is_exact_timestamp: tweet.has_exact_timestamp(),
+            text: tweet.text,
+            in_reply_to_user_id: tweet.in_reply_to_user_id,
+            lang: tweet.lang,
+            travel_speed_from_last_tweet_kmh: None,
+            transport_mode_from_last_tweet: None,
+            place: tweet.place.as_ref().map(PlaceInfo::from),
+        };
+        match movements.entry(tweet.user.id) {
+            Entry::Occupied(mut occ) => {
+                occ.get_mut().points.push(movement_point);
+            }
+            Entry::Vacant(vac) => {
+                vac.insert(UserMovement {
+                    user_id: tweet.user.id,
+                    user_name: tweet.user.name,
+                    user_screen_name: tweet.user.screen_name,
+                    points: vec![movement_point],
+                    outliers_removed: 0,
+                });
+            }
+        }
+        Ok(Added::Point)
+    } else {
+        Ok(Added::NoLocation)
+    }
+}
+
+/// remove points of tweets which have already been seen before, keeping the first
+/// occurrence. Returns the number of removed points.
+///
+/// Overlapping dumps contain the same tweets multiple times.
+fn deduplicate(points: &mut Vec<MovementPoint>) -> u64 {
+    let len_before = points.len();
+    let mut seen = HashSet::with_capacity(len_before);
+    points.retain(|mp| seen.insert(mp.tweet_id));
+    (len_before - points.len()) as u64
+}
+
+/// append the points of `source` to the users in `target`.
+///
+/// The user metadata of the first occurrence of a user wins.
+pub(crate) fn merge_movements(target: &mut Movements, source: Movements) {
+    for (user_id, user_movement) in source {
+        match target.entry(user_id) {
+            Entry::Occupied(mut occ) => {
+                occ.get_mut().points.extend(user_movement.points);
+            }
+            Entry::Vacant(vac) => {
+                vac.insert(user_movement);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        parse_lines, parse_movements, parse_movements_spilled, update_movements, IngestOptions,
+        MovementsBuilder, RetweetPolicy,
+    };
+    use crate::algo::{SortChronologically, Speed};
+    use crate::filter::{parse_bbox, parse_datetime, FilterReason, TweetFilter};
+    use crate::ingest::Movements;
+    use crate::input::Input;
+    use crate::report::{ErrorKind, IngestReport};
+    use crate::select::{SelectionRule, UserSelection};
+    use crate::spill::{SpillOptions, MAX_BUCKETS};
+    use crate::tweet::{parse_line, PlaceType};
+    use serde_json::json;
+    use std::fs::{read_to_string, File};
+    use std::io::Write;
+    use uom::si::f64::Velocity;
+    use uom::si::velocity::kilometer_per_hour;
+
+    fn tweet_line(id: u64, user_id: u64, created_at: &str, x: f64, y: f64) -> String {
+        json!({
+            "id": id,
+            "text": format!("tweet {}", id),
+            "created_at": created_at,
+            "user": {"id": user_id, "name": "name", "screen_name": "screen_name"},
+            "coordinates": {"type": "Point", "coordinates": [x, y]},
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn parallel_equals_sequential() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut lines = Vec::new();
+        let mut inputs = Vec::new();
+        for file_i in 0..4_u64 {
+            let path = dir.path().join(format!("{}.jsonl", file_i));
+            let mut f = File::create(&path).unwrap();
+            for i in 0..50_u64 {
+                let id = file_i * 100 + i;
+                let line = tweet_line(
+                    id,
+                    id % 3,
+                    // some identical timestamps to check the order is stable
+                    &format!("Fri Sep 18 18:{:02}:15 +0000 2020", (id / 2) % 60),
+                    id as f64 / 10.0,
+                    10.0,
+                );
+                writeln!(f, "{}", line).unwrap();
+                lines.push(format!("{}\n", line).into_bytes());
+            }
+            inputs.push(Input::File(path));
+        }
+
+        // small chunks to have multiple chunks per file
+        let options = IngestOptions {
+            chunk_lines: 7,
+            ..Default::default()
+        };
+        let (parallel, report) = parse_movements(&inputs, &options).unwrap();
+        assert_eq!(report.tweets_with_location, 200);
+
+        let (mut sequential, _) = parse_lines(&lines, 1, &Default::default());
+        sequential.retain(|_, v| v.points.len() >= 2);
+        sequential
+            .values_mut()
+            .for_each(|v| v.points.sort_chronologically());
+
+        assert_eq!(parallel.len(), 3);
+        assert_eq!(parallel, sequential);
+
+        // a single pass and two passes over the inputs
+        for buckets in [2, MAX_BUCKETS + 1] {
+            let spill_options = SpillOptions {
+                // spill after every chunk
+                memory_budget_bytes: 1,
+                buckets: Some(buckets),
+                directory: Some(dir.path().to_path_buf()),
+            };
+            let mut spilled = Movements::new();
+            let spilled_report = parse_movements_spilled(&inputs, &options, &spill_options, |m| {
+                spilled.extend(m);
+                Ok(())
+            })
+            .unwrap();
+            assert_eq!(spilled_report, report);
+            assert_eq!(spilled, parallel);
+        }
+    }
+
+    #[test]
+    fn report_and_quarantine_rejected_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tweets.jsonl");
+        let mut f = File::create(&path).unwrap();
+        let created_at = "Fri Sep 18 18:36:15 +0000 2020";
+        writeln!(f, "{}", tweet_line(1, 1, created_at, 1.0, 1.0)).unwrap();
+        writeln!(f, "{{\"id\": 2, \"tex").unwrap();
+        writeln!(f, "{{\"id\": 3}}").unwrap();
+        writeln!(f, "{}", tweet_line(4, 1, created_at, 1.0, 1.0)).unwrap();
+        let bad_geometry = json!({
+            "id": 5,
+            "text": "",
+            "created_at": created_at,
+            "user": {"id": 1, "name": "name", "screen_name": "screen_name"},
+            "coordinates": {"type": "LineString", "coordinates": [[1.0, 1.0], [2.0, 2.0]]},
+        });
+        writeln!(f, "{}", bad_geometry).unwrap();
+        drop(f);
+
+        let options = IngestOptions {
+            quarantine: Some(dir.path().join("quarantine.jsonl")),
+            ..Default::default()
+        };
+        let (movements, report) = parse_movements(&[Input::File(path.clone())], &options).unwrap();
+        assert_eq!(movements.len(), 1);
+        assert_eq!(report.lines, 5);
+        assert_eq!(report.errors.values().sum::<u64>(), 3);
+        assert_eq!(report.errors[&ErrorKind::MalformedJson], 1);
+        assert_eq!(report.errors[&ErrorKind::NotATweet], 1);
+        assert_eq!(report.errors[&ErrorKind::InvalidGeometry], 1);
+        assert_eq!(
+            report.inputs[0]
+                .rejected_lines
+                .iter()
+                .map(|rl| rl.line)
+                .collect::<Vec<_>>(),
+            vec![2, 3, 5]
+        );
+        let quarantined = read_to_string(dir.path().join("quarantine.jsonl")).unwrap();
+        assert_eq!(quarantined.lines().count(), 3);
+        assert!(quarantined.starts_with("{\"id\": 2, \"tex\n"));
+
+        let options = IngestOptions {
+            max_errors: Some(2),
+            ..Default::default()
+        };
+        assert!(parse_movements(&[Input::File(path)], &options).is_err());
+    }
+
+    #[test]
+    fn reject_lines_once() {
+        let created_at = "Fri Sep 18 18:36:15 +0000 2020";
+        let line_string = json!({"type": "LineString", "coordinates": [[1.0, 1.0], [2.0, 2.0]]});
+
+        // a v2 response with two tweets with invalid geometries
+        let v2_tweet = |id: &str| {
+            json!({
+                "id": id,
+                "text": "",
+                "author_id": "10",
+                "created_at": "2020-09-18T18:36:15.000Z",
+                "geo": {"coordinates": line_string},
+            })
+        };
+        let response = json!({
+            "data": [v2_tweet("1"), v2_tweet("2")],
+            "includes": {"users": [{"id": "10", "name": "name", "username": "username"}]},
+        });
+
+        // a tweet with an invalid geometry quoting a valid tweet
+        let mut quote: serde_json::Value =
+            serde_json::from_str(&tweet_line(3, 20, created_at, 1.0, 1.0)).unwrap();
+        quote["coordinates"] = line_string;
+        quote["quoted_status"] =
+            serde_json::from_str(&tweet_line(4, 30, created_at, 2.0, 2.0)).unwrap();
+
+        let lines = vec![
+            response.to_string().into_bytes(),
+            quote.to_string().into_bytes(),
+        ];
+        let options = IngestOptions {
+            retweet_policy: RetweetPolicy::Original,
+            ..Default::default()
+        };
+        let (movements, report) = parse_lines(&lines, 1, &options);
+        assert_eq!(report.tweets, 3);
+        assert_eq!(report.errors[&ErrorKind::InvalidGeometry], 2);
+        assert_eq!(
+            report
+                .rejected_lines
+                .iter()
+                .map(|rl| rl.line)
+                .collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert_eq!(movements.keys().collect::<Vec<_>>(), vec![&30]);
+        assert_eq!(movements[&30].points[0].tweet_id, 4);
+    }
+
+    #[test]
+    fn deduplicate_overlapping_inputs() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut inputs = Vec::new();
+        for (file_i, ids) in [(0, 0..6_u64), (1, 4..10_u64)] {
+            let path = dir.path().join(format!("{}.jsonl", file_i));
+            let mut f = File::create(&path).unwrap();
+            for id in ids {
+                let created_at = format!("Fri Sep 18 18:{:02}:15 +0000 2020", id);
+                writeln!(f, "{}", tweet_line(id, 1, &created_at, id as f64, 1.0)).unwrap();
+            }
+            inputs.push(Input::File(path));
+        }
+
+        let (movements, report) = parse_movements(&inputs, &Default::default()).unwrap();
+        assert_eq!(report.duplicates_dropped, 2);
+        assert_eq!(
+            movements[&1]
+                .points
+                .iter()
+                .map(|mp| mp.tweet_id)
+                .collect::<Vec<_>>(),
+            (0..10).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn retweet_policies() {
+        let created_at = "Fri Sep 18 18:36:15 +0000 2020";
+        let original: serde_json::Value =
+            serde_json::from_str(&tweet_line(1, 10, created_at, 1.0, 1.0)).unwrap();
+        let mut retweet: serde_json::Value =
+            serde_json::from_str(&tweet_line(2, 20, created_at, 2.0, 2.0)).unwrap();
+        retweet["retweeted_status"] = original;
+        let lines = vec![retweet.to_string().into_bytes()];
+
+        let parse = |retweet_policy| {
+            let options = IngestOptions {
+                retweet_policy,
+                ..Default::default()
+            };
+            parse_lines(&lines, 1, &options)
+        };
+
+        let (movements, report) = parse(RetweetPolicy::Skip);
+        assert!(movements.is_empty());
+        assert_eq!(report.retweets, 1);
+
+        let (movements, _) = parse(RetweetPolicy::Keep);
+        assert!(movements[&20].points[0].is_retweet);
+
+        let (movements, _) = parse(RetweetPolicy::Original);
+        assert!(!movements.contains_key(&20));
+        assert!(!movements[&10].points[0].is_retweet);
+        assert_eq!(movements[&10].points[0].tweet_id, 1);
+    }
+
+    #[test]
+    fn drop_coarse_places() {
+        let place = |place_type: &str| {
+            json!({
+                "id": "abc",
+                "place_type": place_type,
+                "full_name": "somewhere",
+                "country_code": "DE",
+                "bounding_box": {
+                    "type": "Polygon",
+                    "coordinates": [[[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0], [0.0, 0.0]]]
+                }
+            })
+        };
+        let created_at = "Fri Sep 18 18:36:15 +0000 2020";
+        let mut lines = Vec::new();
+        for (id, place_type, exact) in [
+            (1, "city", false),
+            (2, "country", false),
+            (3, "admin", true),
+            (4, "admin", false),
+            (5, "unknown", false),
+        ] {
+            let mut tweet: serde_json::Value =
+                serde_json::from_str(&tweet_line(id, 1, created_at, 0.5, 0.5)).unwrap();
+            tweet["place"] = place(place_type);
+            if !exact {
+                tweet["coordinates"] = serde_json::Value::Null;
+            }
+            lines.push(tweet.to_string().into_bytes());
+        }
+
+        let options = IngestOptions {
+            max_place_type: Some(PlaceType::City),
+            ..Default::default()
+        };
+        let (movements, report) = parse_lines(&lines, 1, &options);
+        assert_eq!(report.coarse_places_dropped, 2);
+        assert_eq!(report.untyped_places_kept, 1);
+        assert!(report.filtered.is_empty());
+        let points = &movements[&1].points;
+        assert_eq!(
+            points.iter().map(|mp| mp.tweet_id).collect::<Vec<_>>(),
+            vec![1, 3, 5]
+        );
+        let place_info = points[0].place.as_ref().unwrap();
+        assert_eq!(place_info.place_type, Some(PlaceType::City));
+        assert_eq!(place_info.country_code.as_deref(), Some("DE"));
+    }
+
+    #[test]
+    fn filter_tweets() {
+        let lines = [
+            ("Fri Sep 18 18:36:15 +0000 2020", 1.0),
+            ("Fri Sep 18 18:36:15 +0000 2020", 20.0),
+            ("Fri Sep 25 18:36:15 +0000 2020", 1.0),
+        ]
+        .iter()
+        .enumerate()
+        .map(|(id, (created_at, x))| tweet_line(id as u64, 1, created_at, *x, 1.0).into_bytes())
+        .collect::<Vec<_>>();
+
+        let options = IngestOptions {
+            filter: TweetFilter {
+                until: Some(parse_datetime("2020-09-20").unwrap()),
+                bbox: Some(parse_bbox("0,0,10,10").unwrap()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let (movements, report) = parse_lines(&lines, 1, &options);
+        assert_eq!(movements[&1].points.len(), 1);
+        assert_eq!(report.tweets_with_location, 3);
+        assert_eq!(report.filtered[&FilterReason::Time], 1);
+        assert_eq!(report.filtered[&FilterReason::Bbox], 1);
+    }
+
+    #[test]
+    fn build_movements_from_tweets() {
+        let lines = (0..6_u64)
+            .map(|id| {
+                tweet_line(
+                    id,
+                    id % 2,
+                    &format!("Fri Sep 18 18:{:02}:15 +0000 2020", 10 - id),
+                    id as f64,
+                    1.0,
+                )
+                .into_bytes()
+            })
+            .collect::<Vec<_>>();
+
+        let mut builder = MovementsBuilder::new(Default::default());
+        for line in lines.iter() {
+            for tweet in parse_line(line).unwrap() {
+                builder.add_tweet(tweet).unwrap();
+            }
+        }
+        let (movements, report) = builder.build();
+        let (mut expected, _) = parse_lines(&lines, 1, &Default::default());
+        expected
+            .values_mut()
+            .for_each(|v| v.points.sort_chronologically());
+        assert_eq!(report.tweets, 6);
+        assert_eq!(report.duplicates_dropped, 0);
+        assert_eq!(report.users, 2);
+        assert_eq!(movements, expected);
+    }
+
+    #[test]
+    fn speed_between_snowflake_timestamps() {
+        // snowflake ids 300 ms apart within the second of `created_at`
+        let id = 1307025659294674945;
+        let lines = [
+            tweet_line(id, 1, "Fri Sep 18 18:36:15 +0000 2020", 0.0, 0.0),
+            tweet_line(
+                id + (300 << 22),
+                1,
+                "Fri Sep 18 18:36:15 +0000 2020",
+                0.001,
+                0.0,
+            ),
+        ]
+        .map(String::into_bytes);
+        let (movements, _) = parse_lines(&lines, 1, &Default::default());
+        let points = &movements[&1].points;
+        assert!(points.iter().all(|mp| mp.is_exact_timestamp));
+        let speeds = points.speeds();
+        assert_eq!(speeds.len(), 1);
+        // ~111 m in 300 ms
+        let kmh = speeds[0].unwrap().get::<kilometer_per_hour>();
+        assert!((kmh - 1336.0).abs() < 1.0, "{}", kmh);
+    }
+
+    #[test]
+    fn update_existing_movements() {
+        let parse = |tweets: &[(u64, u64, u32, f64)]| {
+            let lines = tweets
+                .iter()
+                .map(|(id, user_id, minute, x)| {
+                    tweet_line(
+                        *id,
+                        *user_id,
+                        &format!("Fri Sep 18 18:{:02}:15 +0000 2020", minute),
+                        *x,
+                        1.0,
+                    )
+                    .into_bytes()
+                })
+                .collect::<Vec<_>>();
+            parse_lines(&lines, 1, &Default::default()).0
+        };
+
+        let mut existing = parse(&[
+            (1, 1, 10, 1.0),
+            (2, 1, 20, 2.0),
+            (3, 2, 10, 1.0),
+            (4, 2, 20, 2.0),
+        ]);
+        let user_2 = existing[&2].clone();
+
+        let new = parse(&[(2, 1, 20, 2.0), (5, 1, 15, 3.0), (6, 3, 10, 1.0)]);
+        let mut report = IngestReport::default();
+        update_movements(
+            &mut existing,
+            new,
+            None,
+            &UserSelection::default(),
+            &mut report,
+        );
+
+        assert_eq!(
+            existing[&1]
+                .points
+                .iter()
+                .map(|mp| mp.tweet_id)
+                .collect::<Vec<_>>(),
+            vec![1, 5, 2]
+        );
+        assert_eq!(existing[&2], user_2);
+        assert!(!existing.contains_key(&3));
+        assert_eq!(report.duplicates_dropped, 1);
+        assert_eq!(report.users_updated, 1);
+        assert_eq!(report.users, 2);
+        assert_eq!(report.users_dropped[&SelectionRule::MinPoints], 1);
+    }
+
+    #[test]
+    fn update_equals_full_run_with_outliers() {
+        let options = IngestOptions {
+            max_speed: Some(Velocity::new::<kilometer_per_hour>(1000.0)),
+            ..Default::default()
+        };
+        let build = |options: &IngestOptions, tweets: &[(u64, f64)]| {
+            let mut builder = MovementsBuilder::new(options.clone());
+            for (id, x) in tweets {
+                let created_at = format!("Fri Sep 18 {:02}:00:00 +0000 2020", id);
+                builder
+                    .add_tweet(
+                        serde_json::from_str(&tweet_line(*id, 1, &created_at, *x, 1.0)).unwrap(),
+                    )
+                    .unwrap();
+            }
+            builder.build()
+        };
+        let old_tweets = [(1, 0.0), (2, 0.1), (3, 0.2), (4, 0.3)];
+        let new_tweets = [(5, 0.4), (6, 90.0), (7, 90.1)];
+
+        let (mut existing, _) = build(&options, &old_tweets);
+        let (new, mut report) = build(&options.for_update(), &new_tweets);
+        update_movements(
+            &mut existing,
+            new,
+            options.max_speed,
+            &options.selection,
+            &mut report,
+        );
+
+        let (full, full_report) = build(&options, &[&old_tweets[..], &new_tweets[..]].concat());
+        assert_eq!(existing, full);
+        assert_eq!(report.outliers_removed, full_report.outliers_removed);
+        assert!(existing[&1].points.iter().any(|mp| mp.tweet_id == 5));
+    }
+}
@@ -0,0 +1,272 @@
+use bzip2::read::MultiBzDecoder;
+use flate2::read::MultiGzDecoder;
+use std::fmt;
+use std::fs::File;
+use std::io::{stdin, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+const BZIP2_MAGIC: &[u8] = b"BZh";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+    Bzip2,
+}
+
+impl Compression {
+    /// detect the compression from the file extension
+    pub fn from_extension<P: AsRef<Path>>(path: P) -> Option<Self> {
+        match path.as_ref().extension()?.to_str()? {
+            "gz" | "gzip" => Some(Self::Gzip),
+            "zst" | "zstd" => Some(Self::Zstd),
+            "bz2" | "bzip2" => Some(Self::Bzip2),
+            "jsonl" | "json" => Some(Self::None),
+            _ => None,
+        }
+    }
+
+    /// detect the compression from the first bytes of a stream
+    pub fn from_magic_bytes(bytes: &[u8]) -> Self {
+        if bytes.starts_with(GZIP_MAGIC) {
+            Self::Gzip
+        } else if bytes.starts_with(ZSTD_MAGIC) {
+            Self::Zstd
+        } else if bytes.starts_with(BZIP2_MAGIC) {
+            Self::Bzip2
+        } else {
+            Self::None
+        }
+    }
+}
+
+/// open a - possibly compressed - file for line-wise reading.
+///
+/// The compression is detected by the file extension and falls back to the magic bytes
+/// at the start of the file when the extension is not known.
+pub fn open_jsonl<P: AsRef<Path>>(path: P) -> eyre::Result<Box<dyn BufRead + Send>> {
+    let mut bufreader = BufReader::new(File::open(path.as_ref())?);
+    let compression = match Compression::from_extension(path.as_ref()) {
+        Some(compression) => compression,
+        None => Compression::from_magic_bytes(bufreader.fill_buf()?),
+    };
+    decompress(bufreader, compression)
+}
+
+/// a source of JSONL tweets
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Input {
+    Stdin,
+    File(PathBuf),
+}
+
+impl Input {
+    /// expand the arguments given on the command line to the inputs to read.
+    ///
+    /// * `-` is stdin
+    /// * directories are walked recursively for `*.jsonl*` files, sorted by path
+    /// * arguments containing glob characters are expanded, sorted by path
+    /// * everything else is taken as a file path
+    ///
+    /// The order of the arguments themselves is preserved. Directories and globs without any
+    /// matching files are an error, as they are most likely a typo.
+    pub fn expand(args: &[String]) -> eyre::Result<Vec<Self>> {
+        let mut inputs = Vec::with_capacity(args.len());
+        for arg in args {
+            if arg == "-" {
+                inputs.push(Self::Stdin);
+            } else if Path::new(arg).is_dir() {
+                let mut paths = Vec::new();
+                for entry in WalkDir::new(arg).follow_links(true) {
+                    let entry = entry?;
+                    if entry.file_type().is_file() && is_jsonl_filename(entry.path()) {
+                        paths.push(entry.into_path());
+                    }
+                }
+                if paths.is_empty() {
+                    return Err(eyre::eyre!("directory {} contains no *.jsonl* files", arg));
+                }
+                paths.sort();
+                inputs.extend(paths.into_iter().map(Self::File));
+            } else if arg.contains(['*', '?', '[']) {
+                let mut paths = Vec::new();
+                for path in glob::glob(arg)? {
+                    let path = path?;
+                    if !path.is_dir() {
+                        paths.push(path);
+                    }
+                }
+                if paths.is_empty() {
+                    return Err(eyre::eyre!("{} does not match any files", arg));
+                }
+                paths.sort();
+                inputs.extend(paths.into_iter().map(Self::File));
+            } else {
+                inputs.push(Self::File(PathBuf::from(arg)));
+            }
+        }
+        Ok(inputs)
+    }
+
+    /// open the input for line-wise reading, decompressing it if required
+    pub fn open(&self) -> eyre::Result<Box<dyn BufRead + Send>> {
+        match self {
+            Self::Stdin => {
+                let mut bufreader = BufReader::new(stdin());
+                let compression = Compression::from_magic_bytes(bufreader.fill_buf()?);
+                decompress(bufreader, compression)
+            }
+            Self::File(path) => open_jsonl(path),
+        }
+    }
+}
+
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Stdin => write!(f, "-"),
+            Self::File(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+fn is_jsonl_filename(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| name.contains(".jsonl"))
+        .unwrap_or(false)
+}
+
+/// wrap the reader in the decoder matching `compression`
+pub fn decompress<R>(reader: R, compression: Compression) -> eyre::Result<Box<dyn BufRead + Send>>
+where
+    R: BufRead + Send + 'static,
+{
+    Ok(match compression {
+        Compression::None => Box::new(reader),
+        Compression::Gzip => Box::new(BufReader::new(MultiGzDecoder::new(reader))),
+        Compression::Zstd => Box::new(BufReader::new(zstd::Decoder::with_buffer(reader)?)),
+        Compression::Bzip2 => Box::new(BufReader::new(MultiBzDecoder::new(reader))),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decompress, Compression, Input};
+    use flate2::write::GzEncoder;
+    use std::fs::{create_dir_all, File};
+    use std::io::{BufRead, Cursor, Write};
+    use std::path::PathBuf;
+
+    const LINES: &str = "{\"a\": 1}\n{\"a\": 2}\n";
+
+    fn read_lines(compressed: Vec<u8>) -> Vec<String> {
+        let compression = Compression::from_magic_bytes(&compressed);
+        decompress(Cursor::new(compressed), compression)
+            .unwrap()
+            .lines()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn detect_by_extension() {
+        assert_eq!(
+            Compression::from_extension("tweets.jsonl.gz"),
+            Some(Compression::Gzip)
+        );
+        assert_eq!(
+            Compression::from_extension("tweets.jsonl.zst"),
+            Some(Compression::Zstd)
+        );
+        assert_eq!(
+            Compression::from_extension("tweets.jsonl.bz2"),
+            Some(Compression::Bzip2)
+        );
+        assert_eq!(
+            Compression::from_extension("tweets.jsonl"),
+            Some(Compression::None)
+        );
+        assert_eq!(Compression::from_extension("tweets"), None);
+    }
+
+    #[test]
+    fn gzip_roundtrip() {
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(LINES.as_bytes()).unwrap();
+        assert_eq!(read_lines(encoder.finish().unwrap()).len(), 2);
+    }
+
+    #[test]
+    fn zstd_roundtrip() {
+        let compressed = zstd::encode_all(LINES.as_bytes(), 0).unwrap();
+        assert_eq!(read_lines(compressed).len(), 2);
+    }
+
+    #[test]
+    fn bzip2_roundtrip() {
+        let mut encoder = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+        encoder.write_all(LINES.as_bytes()).unwrap();
+        assert_eq!(read_lines(encoder.finish().unwrap()).len(), 2);
+    }
+
+    #[test]
+    fn uncompressed_passthrough() {
+        assert_eq!(read_lines(LINES.as_bytes().to_vec()).len(), 2);
+    }
+
+    #[test]
+    fn expand_directory_and_stdin() {
+        let dir = tempfile::tempdir().unwrap();
+        create_dir_all(dir.path().join("b")).unwrap();
+        for name in ["b/2.jsonl.gz", "a.jsonl", "b/1.jsonl", "notes.txt"] {
+            File::create(dir.path().join(name)).unwrap();
+        }
+
+        let inputs =
+            Input::expand(&["-".to_string(), dir.path().to_str().unwrap().to_string()]).unwrap();
+        assert_eq!(
+            inputs,
+            vec![
+                Input::Stdin,
+                Input::File(dir.path().join("a.jsonl")),
+                Input::File(dir.path().join("b/1.jsonl")),
+                Input::File(dir.path().join("b/2.jsonl.gz")),
+            ]
+        );
+
+        let inputs = Input::expand(&[format!("{}/b/*.jsonl*", dir.path().display())]).unwrap();
+        assert_eq!(
+            inputs,
+            vec![
+                Input::File(dir.path().join("b/1.jsonl")),
+                Input::File(dir.path().join("b/2.jsonl.gz")),
+            ]
+        );
+        assert_eq!(
+            Input::expand(&["some.jsonl".to_string()]).unwrap(),
+            vec![Input::File(PathBuf::from("some.jsonl"))]
+        );
+
+        // directories matched by a glob are skipped
+        let inputs = Input::expand(&[format!("{}/*", dir.path().display())]).unwrap();
+        assert_eq!(
+            inputs,
+            vec![
+                Input::File(dir.path().join("a.jsonl")),
+                Input::File(dir.path().join("notes.txt")),
+            ]
+        );
+    }
+
+    #[test]
+    fn expand_without_matches() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(Input::expand(&[format!("{}/*.jsonl", dir.path().display())]).is_err());
+        assert!(Input::expand(&[dir.path().to_str().unwrap().to_string()]).is_err());
+    }
+}
@@ -0,0 +1,19 @@
+//! Reconstruct the movements of twitter users from their geolocated tweets.
+//!
+//! Tweets are read from JSONL files with [`ingest::parse_movements`], or passed one by one
+//! to a [`ingest::MovementsBuilder`]. The resulting [`model::UserMovement`]s provide metrics
+//! and implement the traits of [`algo`].
+
+pub mod airports;
+pub mod algo;
+pub mod bot;
+pub mod filter;
+pub mod ingest;
+pub mod input;
+pub mod model;
+pub mod network;
+pub mod output;
+pub mod report;
+pub mod select;
+pub mod spill;
+pub mod tweet;
@@ -1,20 +1,34 @@
-mod algo;
-mod model;
-mod tweet;
-
-use crate::algo::speed::speed;
-use crate::algo::SortChronologically;
-use crate::algo::Speed;
-use crate::model::{MovementPoint, UserMovement};
-use crate::tweet::Tweet;
+use chrono::{DateTime, Duration, Utc};
 use clap::{Args, Parser, Subcommand};
-use geo_types::{Coordinate, LineString};
-use geojson::{Feature, FeatureCollection, GeoJson, Value};
+use geo_types::Rect;
 use serde_json::{to_value, Map};
-use std::collections::hash_map::Entry;
-use std::collections::HashMap;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{stdout, BufReader, BufWriter};
+use std::path::PathBuf;
+use std::sync::Arc;
+use twitter_user_movement::airports::Airports;
+use twitter_user_movement::algo::flights::FlightOptions;
+use twitter_user_movement::algo::home_work::HomeWorkOptions;
+use twitter_user_movement::algo::segmentation::SegmentationOptions;
+use twitter_user_movement::algo::speed::SpeedBound;
+use twitter_user_movement::algo::staypoints::StayPointOptions;
+use twitter_user_movement::filter::{parse_bbox, parse_datetime, Area, TweetFilter};
+use twitter_user_movement::ingest::{
+    parse_movements, parse_movements_spilled, update_movements, IngestOptions, Movements,
+    RetweetPolicy,
+};
+use twitter_user_movement::input::Input;
+use twitter_user_movement::model::MetricsOptions;
+use twitter_user_movement::network::RoadNetwork;
+use twitter_user_movement::output::{
+    read_movement_json, GeoJsonWriter, MovementJsonWriter, MovementsWriter, StayPointWriter,
+};
+use twitter_user_movement::report::IngestReport;
+use twitter_user_movement::select::{parse_duration, UserSelection};
+use twitter_user_movement::spill::SpillOptions;
+use twitter_user_movement::tweet::PlaceType;
+use uom::si::f64::{Length, Velocity};
+use uom::si::length::{kilometer, meter};
 use uom::si::velocity::kilometer_per_hour;
 
 #[derive(Parser, Debug)]
@@ -24,169 +38,471 @@ struct Cli {
     command: Command,
 }
 
+// the subcommands are named after their output
+#[allow(clippy::enum_variant_names)]
 #[derive(Subcommand, Debug)]
 enum Command {
     /// Convert JSONL-files containing tweets to a custom JSON file containing the movements for each user.
     ///
-    /// The JSON will be written to stdout
-    ToMovementJson(FileList),
+    /// The JSON will be written to stdout. The movements are keyed by the user id, the filters
+    /// and the settings the speeds were computed with are written under the "metadata" key.
+    ToMovementJson(MovementJsonArgs),
     /// Convert JSONL-files containing tweets to a GeoJSON FeatureCollection containing a LineString for each user.
     ///
+    /// With --trips or --segments a LineString is written for each trip or each segment.
+    ///
+    /// The JSON will be written to stdout
+    ToGeoJson(GeoJsonArgs),
+    /// Detect stay points in the movements of each user and write their significant places as
+    /// GeoJSON FeatureCollection of Points.
+    ///
     /// The JSON will be written to stdout
-    ToGeoJson(FileList),
+    ToStaypoints(StayPointArgs),
 }
 
 #[derive(Args, Debug)]
-struct FileList {
-    /// JSONL files containing tweets
-    jsonl_files: Vec<String>,
-}
+struct GeoJsonArgs {
+    #[clap(flatten)]
+    file_list: FileList,
 
-type Movements = HashMap<u64, UserMovement>;
+    /// Split the movements into trips and write a LineString for each trip instead of each user.
+    #[clap(long)]
+    trips: bool,
 
-fn main() -> eyre::Result<()> {
-    let args = Cli::parse();
+    /// Start a new trip when two consecutive points are further apart in time than this
+    /// duration. A number with an optional unit: s, m, h or d.
+    #[clap(long, value_parser = parse_duration, default_value = "6h", requires = "trips")]
+    trip_max_gap: Duration,
 
-    match &args.command {
-        Command::ToGeoJson(file_list) => {
-            let movements = parse_movements(&file_list.jsonl_files)?;
-            save_geojson(movements)?;
+    /// Start a new trip when two consecutive points are further apart than this distance in
+    /// kilometers.
+    #[clap(long, requires = "trips")]
+    trip_max_distance_km: Option<f64>,
+
+    /// Write a LineString for each segment between two consecutive points instead of each
+    /// user, with the timestamps, duration, distance, speed, bearing, turning angle and
+    /// transport mode of the segment.
+    #[clap(long, conflicts_with = "trips")]
+    segments: bool,
+
+    /// Route between the points along the LineStrings of this GeoJSON file, for example a road
+    /// network. The LineStrings of the output follow the routes and the speeds and transport
+    /// modes are computed from the network distances.
+    #[clap(long)]
+    network: Option<PathBuf>,
+
+    /// The maximum distance in meters of a point from the nearest node of the network to be
+    /// routed. Points further away are connected by a straight line.
+    #[clap(long, default_value_t = 200.0, requires = "network")]
+    network_snap_distance_m: f64,
+}
+
+impl GeoJsonArgs {
+    fn segmentation(&self) -> Option<SegmentationOptions> {
+        if !self.trips {
+            return None;
+        }
+        Some(SegmentationOptions {
+            max_gap: self.trip_max_gap,
+            max_distance: self.trip_max_distance_km.map(Length::new::<kilometer>),
+        })
+    }
+
+    fn run(&self) -> eyre::Result<()> {
+        let segmentation = self.segmentation();
+        let ingest_options = self.file_list.ingest_options()?;
+        let mut metadata = metadata(&ingest_options)?;
+        if let Some(segmentation) = segmentation.as_ref() {
+            metadata.insert("trips".to_string(), to_value(segmentation)?);
+        }
+        let mut metrics_options = self.file_list.metrics_options()?;
+        metadata.insert("flights".to_string(), to_value(&metrics_options.flights)?);
+        if let Some(network) = self.network.as_ref() {
+            metadata.insert(
+                "network".to_string(),
+                to_value(network.display().to_string())?,
+            );
+            metrics_options.network = Some(Arc::new(RoadNetwork::from_geojson_file(
+                network,
+                Length::new::<meter>(self.network_snap_distance_m),
+            )?));
+        }
+        let mut writer = GeoJsonWriter::new(BufWriter::new(stdout()), metrics_options, metadata)?;
+        if let Some(segmentation) = segmentation {
+            writer = writer.with_trips(segmentation);
         }
-        Command::ToMovementJson(file_list) => {
-            let movements = parse_movements(&file_list.jsonl_files)?;
-            save_movements(movements)?;
+        if self.segments {
+            writer = writer.with_segments();
         }
+        self.file_list.run(&ingest_options, writer)
     }
-    Ok(())
 }
 
-fn parse_movements(jsonl_files: &[String]) -> eyre::Result<Movements> {
-    let mut movements = Movements::new();
-
-    let mut buf = String::new();
-    for jsonl_filename in jsonl_files.iter() {
-        let mut bufreader = BufReader::new(File::open(jsonl_filename)?);
-        loop {
-            buf.clear();
-            let n_read = bufreader.read_line(&mut buf)?;
-            if n_read == 0 {
-                // EOF
-                break;
-            }
-            let tweet: Tweet = match serde_json::from_str(&buf) {
-                Ok(tweet) => tweet,
-                Err(e) => {
-                    eprintln!("failed to deserialize tweet - {}: {}", e, buf.trim_end());
-                    continue;
-                }
-            };
-
-            if let Some((point, is_exact_location)) = tweet.geo_point()? {
-                let movement_point = MovementPoint {
-                    point,
-                    is_exact_location,
-                    timestamp: tweet.created_at,
-                    text: tweet.text,
-                    in_reply_to_user_id: tweet.in_reply_to_user_id,
-                    lang: tweet.lang,
-                    travel_speed_from_last_tweet_kmh: None,
-                };
-                match movements.entry(tweet.user.id) {
-                    Entry::Occupied(mut occ) => {
-                        occ.get_mut().points.push(movement_point);
-                    }
-                    Entry::Vacant(vac) => {
-                        vac.insert(UserMovement {
-                            user_id: tweet.user.id,
-                            user_name: tweet.user.name,
-                            user_screen_name: tweet.user.screen_name,
-                            points: vec![movement_point],
-                        });
-                    }
-                }
-            }
-        }
+#[derive(Args, Debug)]
+struct StayPointArgs {
+    #[clap(flatten)]
+    file_list: FileList,
+
+    /// The maximum distance in meters of the points of a stay point from its first point.
+    #[clap(long, default_value_t = 200.0)]
+    stay_radius_m: f64,
+
+    /// The minimum duration of a stay point. A number with an optional unit: s, m, h or d.
+    #[clap(long, value_parser = parse_duration, default_value = "20m")]
+    stay_min_duration: Duration,
+
+    /// The maximum distance in meters of stay points from the center of their significant
+    /// place.
+    #[clap(long, default_value_t = 300.0)]
+    place_radius_m: f64,
+
+    /// Write the individual stay points instead of the significant places.
+    #[clap(long)]
+    stay_points: bool,
+}
+
+impl StayPointArgs {
+    fn run(&self) -> eyre::Result<()> {
+        let options = StayPointOptions {
+            radius: Length::new::<meter>(self.stay_radius_m),
+            min_duration: self.stay_min_duration,
+            place_radius: Length::new::<meter>(self.place_radius_m),
+        };
+        let ingest_options = self.file_list.ingest_options()?;
+        let mut metadata = metadata(&ingest_options)?;
+        metadata.insert("stay_points".to_string(), to_value(&options)?);
+        self.file_list.run(
+            &ingest_options,
+            StayPointWriter::new(
+                BufWriter::new(stdout()),
+                options,
+                !self.stay_points,
+                metadata,
+            )?,
+        )
     }
+}
 
-    // remove all with less than two points
-    movements.retain(|_, v| v.points.len() >= 2);
+#[derive(Args, Debug)]
+struct MovementJsonArgs {
+    #[clap(flatten)]
+    file_list: FileList,
 
-    // sort by time
-    movements.iter_mut().for_each(|(_, v)| {
-        v.points.sort_chronologically();
-    });
-    Ok(movements)
+    /// Merge the tweets into the movements of this file, written by an earlier run. Only the
+    /// users with new points are checked against the selection again. The travel speeds and
+    /// transport modes of all users are recomputed with the current settings.
+    ///
+    /// The file is read completely before writing the output, but should not be the same file
+    /// stdout is redirected to.
+    #[clap(long, conflicts_with = "memory-budget")]
+    update: Option<PathBuf>,
 }
 
-fn save_geojson(user_movements: HashMap<u64, UserMovement>) -> eyre::Result<()> {
-    let mut features = Vec::with_capacity(user_movements.len());
-    for (_, user_movement) in user_movements {
-        let coordinates: Vec<Coordinate<f64>> = user_movement
-            .points
-            .iter()
-            .map(|tp| tp.clone().into())
-            .collect();
-        let linestring = LineString::from(coordinates);
-
-        let metrics = user_movement.metrics();
-
-        let mut props = Map::new();
-        props.insert("sp_pc_10".to_string(), to_value(metrics.speeds_kmh_pc_10)?);
-        props.insert("sp_pc_50".to_string(), to_value(metrics.speeds_kmh_pc_50)?);
-        props.insert("sp_pc_80".to_string(), to_value(metrics.speeds_kmh_pc_80)?);
-        props.insert(
-            "sp_pc_100".to_string(),
-            to_value(metrics.speeds_kmh_pc_100)?,
-        );
-        props.insert(
-            "straightness_median".to_string(),
-            to_value(metrics.straightness_median)?,
-        );
+#[derive(Args, Debug)]
+struct FileList {
+    /// JSONL files containing Twitter API v1.1 tweets or v2 responses. gzip, zstd and bzip2
+    /// compressed files are decompressed transparently.
+    ///
+    /// `-` reads from stdin, directories are searched recursively for `*.jsonl*` files.
+    #[clap(required = true)]
+    jsonl_files: Vec<String>,
 
-        props.insert(
-            "max_speed_kmh".to_string(),
-            to_value(
-                user_movement
-                    .points
-                    .speed_max()
-                    .map(|v| v.get::<kilometer_per_hour>()),
-            )?,
-        );
-        props.insert("user_name".to_string(), to_value(user_movement.user_name)?);
-        props.insert("user_id".to_string(), to_value(user_movement.user_id)?);
-        props.insert(
-            "user_screen_name".to_string(),
-            to_value(user_movement.user_screen_name)?,
-        );
+    /// Write a JSON report of the ingestion to this file. The report contains the number of
+    /// rejected lines per input and error kind together with their line numbers.
+    #[clap(long)]
+    report: Option<PathBuf>,
+
+    /// Abort when more than this number of lines had to be rejected.
+    #[clap(long)]
+    max_errors: Option<u64>,
+
+    /// Write rejected lines unchanged to this file.
+    #[clap(long)]
+    quarantine: Option<PathBuf>,
+
+    /// How to treat retweets: "skip" them, "keep" them as observations of the retweeting
+    /// user, or use the embedded "original" tweets and quoted tweets as observations of their
+    /// authors.
+    #[clap(long, default_value_t = RetweetPolicy::Keep)]
+    retweets: RetweetPolicy,
+
+    /// Drop tweets which are only located by a place coarser than this place type.
+    /// The place types from fine to coarse: poi, neighborhood, city, admin, country.
+    /// Places without a type are kept and counted in the report.
+    #[clap(long)]
+    max_place_type: Option<PlaceType>,
+
+    /// Only use tweets created at or after this time. RFC 3339 timestamp or date (YYYY-MM-DD).
+    #[clap(long, value_parser = parse_datetime)]
+    since: Option<DateTime<Utc>>,
 
-        features.push(Feature {
-            bbox: None,
-            geometry: Some(geojson::Geometry::new(Value::from(&linestring))),
-            id: None,
-            properties: Some(props),
-            foreign_members: None,
+    /// Only use tweets created before this time. RFC 3339 timestamp or date (YYYY-MM-DD).
+    #[clap(long, value_parser = parse_datetime)]
+    until: Option<DateTime<Utc>>,
+
+    /// Only use tweets located within this bounding box, given as min_x,min_y,max_x,max_y.
+    #[clap(long, value_parser = parse_bbox, allow_hyphen_values = true)]
+    bbox: Option<Rect<f64>>,
+
+    /// Only use tweets located within the polygons of this GeoJSON file.
+    #[clap(long)]
+    within: Option<PathBuf>,
+
+    /// Only use tweets in these languages. Can be given multiple times or comma-separated.
+    #[clap(long, use_value_delimiter = true)]
+    lang: Vec<String>,
+
+    /// Remove points which can only be reached with a speed above this number of km/h, for
+    /// example caused by a single bad coordinate. The neighbouring points decide which point of
+    /// an implausible segment is removed.
+    #[clap(long)]
+    max_speed_kmh: Option<f64>,
+
+    /// Drop users with less than this number of points.
+    #[clap(long, default_value_t = 2)]
+    min_points: usize,
+
+    /// Drop users with more than this number of points.
+    #[clap(long)]
+    max_points: Option<usize>,
+
+    /// Drop users whose first and last points are less than this duration apart. A number
+    /// with an optional unit: s, m, h or d. Plain numbers are seconds.
+    #[clap(long, value_parser = parse_duration)]
+    min_duration: Option<Duration>,
+
+    /// Drop users covering less than this distance in kilometers along all their points.
+    #[clap(long)]
+    min_distance_km: Option<f64>,
+
+    /// Drop users whose bot score is above this threshold. The score ranges from 0 to 1 and
+    /// combines the regularity of the posting intervals, the share of identical locations and
+    /// the share of templated texts.
+    #[clap(long)]
+    max_bot_score: Option<f64>,
+
+    /// The estimate of the speeds between tweets located by places with an uncertain position:
+    /// "nominal" ignores the uncertainty, "lower" and "upper" compute the lowest and highest
+    /// possible speed.
+    #[clap(long, default_value_t = SpeedBound::Nominal)]
+    speed_bound: SpeedBound,
+
+    /// The maximum distance in meters of the supporting tweets from the inferred home and work
+    /// locations.
+    #[clap(long, default_value_t = 300.0)]
+    home_work_radius_m: f64,
+
+    /// Label segments faster than this number of km/h as flights, when they also cover the
+    /// --flight-min-distance-km.
+    #[clap(long, default_value_t = 250.0)]
+    flight_min_speed_kmh: f64,
+
+    /// The minimum distance in kilometers covered by a flight.
+    #[clap(long, default_value_t = 100.0)]
+    flight_min_distance_km: f64,
+
+    /// Snap the endpoints of flights to the nearest large or medium airport of this CSV file.
+    /// Accepts the airports.csv of OurAirports or a file with the columns code, name, latitude
+    /// and longitude.
+    #[clap(long)]
+    airports: Option<PathBuf>,
+
+    /// The maximum distance in kilometers of the endpoint of a flight from its airport.
+    #[clap(long, default_value_t = 50.0, requires = "airports")]
+    airport_snap_distance_km: f64,
+
+    /// Leave flights out of the speed statistics.
+    #[clap(long)]
+    exclude_flights: bool,
+
+    /// Limit the memory used for the movements to about this number of MiB by partitioning
+    /// the users into buckets on disk and processing one bucket at a time. Budgets needing more
+    /// than 128 buckets read the input files several times, which is not possible for stdin.
+    #[clap(long)]
+    memory_budget: Option<u64>,
+
+    /// The number of buckets to use with --memory-budget. Estimated from the size of the inputs
+    /// by default.
+    #[clap(long, requires = "memory-budget")]
+    spill_buckets: Option<usize>,
+
+    /// The directory to write the buckets to with --memory-budget. Defaults to the temporary
+    /// directory of the system.
+    #[clap(long, requires = "memory-budget")]
+    spill_dir: Option<PathBuf>,
+}
+
+impl FileList {
+    fn inputs(&self) -> eyre::Result<Vec<Input>> {
+        Input::expand(&self.jsonl_files)
+    }
+
+    fn ingest_options(&self) -> eyre::Result<IngestOptions> {
+        Ok(IngestOptions {
+            retweet_policy: self.retweets,
+            max_place_type: self.max_place_type,
+            filter: TweetFilter {
+                since: self.since,
+                until: self.until,
+                bbox: self.bbox,
+                within: self
+                    .within
+                    .as_ref()
+                    .map(Area::from_geojson_file)
+                    .transpose()?,
+                langs: self.lang.clone(),
+            },
+            max_speed: self.max_speed_kmh.map(Velocity::new::<kilometer_per_hour>),
+            selection: UserSelection {
+                min_points: self.min_points,
+                max_points: self.max_points,
+                min_duration: self.min_duration,
+                min_distance: self.min_distance_km.map(Length::new::<kilometer>),
+                max_bot_score: self.max_bot_score,
+            },
+            max_errors: self.max_errors,
+            quarantine: self.quarantine.clone(),
+            ..Default::default()
         })
     }
 
-    let gj = GeoJson::FeatureCollection(FeatureCollection {
-        bbox: None,
-        features,
-        foreign_members: None,
-    });
+    fn flight_options(&self) -> eyre::Result<FlightOptions> {
+        Ok(FlightOptions {
+            min_speed: Velocity::new::<kilometer_per_hour>(self.flight_min_speed_kmh),
+            min_distance: Length::new::<kilometer>(self.flight_min_distance_km),
+            airports: self
+                .airports
+                .as_ref()
+                .map(|path| {
+                    Airports::from_csv_file(
+                        path,
+                        Length::new::<kilometer>(self.airport_snap_distance_km),
+                    )
+                })
+                .transpose()?
+                .map(Arc::new),
+            exclude_from_speeds: self.exclude_flights,
+        })
+    }
 
-    println!("{}", gj);
-    Ok(())
-}
+    fn metrics_options(&self) -> eyre::Result<MetricsOptions> {
+        Ok(MetricsOptions {
+            speed_bound: self.speed_bound,
+            home_work: HomeWorkOptions {
+                radius: Length::new::<meter>(self.home_work_radius_m),
+                ..Default::default()
+            },
+            network: None,
+            flights: self.flight_options()?,
+        })
+    }
+
+    fn spill_options(&self) -> Option<SpillOptions> {
+        self.memory_budget.map(|memory_budget| SpillOptions {
+            memory_budget_bytes: memory_budget * 1024 * 1024,
+            buckets: self.spill_buckets,
+            directory: self.spill_dir.clone(),
+        })
+    }
+
+    /// parse the movements, pass them to the writer and write the report
+    fn run<W: MovementsWriter>(
+        &self,
+        ingest_options: &IngestOptions,
+        mut writer: W,
+    ) -> eyre::Result<()> {
+        let inputs = self.inputs()?;
+        let report = match self.spill_options() {
+            Some(spill_options) => {
+                parse_movements_spilled(&inputs, ingest_options, &spill_options, |movements| {
+                    writer.write_movements(movements)
+                })?
+            }
+            None => {
+                let (movements, report) = parse_movements(&inputs, ingest_options)?;
+                writer.write_movements(movements)?;
+                report
+            }
+        };
+        writer.finish()?;
+        self.write_report(&report)
+    }
+
+    /// parse the movements and merge them into previously computed movements
+    fn run_update<W: MovementsWriter>(
+        &self,
+        ingest_options: &IngestOptions,
+        mut existing: Movements,
+        mut writer: W,
+    ) -> eyre::Result<()> {
+        let inputs = self.inputs()?;
+
+        // the outlier removal and the selection apply to the merged movements
+        let (movements, mut report) = parse_movements(&inputs, &ingest_options.for_update())?;
+        report.selection = ingest_options.selection.clone();
+        update_movements(
+            &mut existing,
+            movements,
+            ingest_options.max_speed,
+            &ingest_options.selection,
+            &mut report,
+        );
+
+        writer.write_movements(existing)?;
+        writer.finish()?;
+        self.write_report(&report)
+    }
 
-fn save_movements(mut user_movements: HashMap<u64, UserMovement>) -> eyre::Result<()> {
-    // enrich with travel speeds first
-    for (_, user) in user_movements.iter_mut() {
-        for idx in 1..user.points.len() {
-            user.points[idx].travel_speed_from_last_tweet_kmh =
-                Some(speed(&user.points[idx - 1], &user.points[idx]).get::<kilometer_per_hour>());
+    fn write_report(&self, report: &IngestReport) -> eyre::Result<()> {
+        eprintln!("{}", report);
+        if let Some(report_path) = self.report.as_ref() {
+            serde_json::to_writer_pretty(BufWriter::new(File::create(report_path)?), report)?;
         }
+        Ok(())
     }
+}
+
+/// metadata describing how the output was created
+fn metadata(ingest_options: &IngestOptions) -> eyre::Result<Map<String, serde_json::Value>> {
+    let mut metadata = Map::new();
+    metadata.insert("filters".to_string(), to_value(&ingest_options.filter)?);
+    metadata.insert(
+        "selection".to_string(),
+        to_value(&ingest_options.selection)?,
+    );
+    Ok(metadata)
+}
+
+fn main() -> eyre::Result<()> {
+    let args = Cli::parse();
 
-    println!("{}", serde_json::to_string(&user_movements)?);
+    match &args.command {
+        Command::ToGeoJson(args) => args.run()?,
+        Command::ToStaypoints(args) => args.run()?,
+        Command::ToMovementJson(args) => {
+            let ingest_options = args.file_list.ingest_options()?;
+            let writer = MovementJsonWriter::new(
+                BufWriter::new(stdout()),
+                args.file_list.speed_bound,
+                metadata(&ingest_options)?,
+            )?
+            .with_flights(args.file_list.flight_options()?);
+            match args.update.as_ref() {
+                Some(path) => {
+                    let existing = read_movement_json(BufReader::new(File::open(path)?))?;
+                    if !writer.same_speed_settings(existing.metadata.as_ref())? {
+                        eprintln!(
+                            "{} was written with other speed settings, the travel speeds and \
+                             transport modes of all users are recomputed",
+                            path.display()
+                        );
+                    }
+                    args.file_list
+                        .run_update(&ingest_options, existing.movements, writer)?
+                }
+                None => args.file_list.run(&ingest_options, writer)?,
+            }
+        }
+    }
     Ok(())
 }
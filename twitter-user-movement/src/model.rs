@@ -1,12 +1,22 @@
+use crate::algo::flights::{label_flights, Flight, FlightOptions};
+use crate::algo::home_work::{HomeWorkOptions, InferredLocation};
+use crate::algo::speed::SpeedBound;
 use crate::algo::straightness::StraightnessChunked;
+use crate::algo::transport_mode::{mode_shares, ModeEstimate, TransportMode, TransportModes};
 use crate::algo::PointInTime;
-use crate::Speed;
+use crate::algo::{Flights, HomeWork, Speed};
+use crate::bot::BotScore;
+use crate::network::{route_speeds, RoadNetwork, Route};
+use crate::tweet::{Place, PlaceType};
 use chrono::{DateTime, Utc};
-use geo_types::{Coordinate, Point};
+use geo_types::{Coord, Point};
 use serde::ser::SerializeStruct;
-use serde::{Serialize, Serializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use statrs::statistics::{Data, OrderStatistics};
-use uom::si::f64::Velocity;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use uom::si::f64::{Length, Velocity};
+use uom::si::length::{kilometer, meter};
 use uom::si::velocity::kilometer_per_hour;
 
 fn point_ser<S>(point: &Point<f64>, serializer: S) -> Result<S::Ok, S::Error>
@@ -19,20 +29,68 @@ where
     state.end()
 }
 
-#[derive(PartialEq, Serialize, Clone, Debug)]
+fn point_de<'de, D>(deserializer: D) -> Result<Point<f64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    struct XY {
+        x: f64,
+        y: f64,
+    }
+    let xy = XY::deserialize(deserializer)?;
+    Ok(Point::new(xy.x, xy.y))
+}
+
+#[derive(PartialEq, Serialize, Deserialize, Clone, Debug)]
 pub struct MovementPoint {
-    #[serde(serialize_with = "point_ser")]
+    pub tweet_id: u64,
+    #[serde(serialize_with = "point_ser", deserialize_with = "point_de")]
     pub point: Point<f64>,
     pub is_exact_location: bool,
+    /// radius in meters around `point` within which the actual location lies
+    pub uncertainty_m: f64,
+    pub is_retweet: bool,
+    /// millisecond precision when derived from the tweet id, see [`crate::tweet::Tweet::timestamp`]
     pub timestamp: DateTime<Utc>,
+    /// whether `timestamp` was derived from the tweet id. Second precision otherwise.
+    #[serde(default)]
+    pub is_exact_timestamp: bool,
 
     pub text: String,
     pub in_reply_to_user_id: Option<u64>,
     pub lang: Option<String>,
+    /// `None` for the first point and for points too close in time to the previous point to
+    /// compute a speed, see [`crate::algo::speed::speed_bounded`]
     pub travel_speed_from_last_tweet_kmh: Option<f64>,
+    /// the transport mode of the segment from the previous point, `None` like the travel speed
+    #[serde(default)]
+    pub transport_mode_from_last_tweet: Option<ModeEstimate>,
+
+    /// the place the tweet was attached to
+    pub place: Option<PlaceInfo>,
+}
+
+#[derive(PartialEq, Serialize, Deserialize, Clone, Debug)]
+pub struct PlaceInfo {
+    pub id: Option<String>,
+    pub place_type: Option<PlaceType>,
+    pub full_name: Option<String>,
+    pub country_code: Option<String>,
 }
 
-impl From<MovementPoint> for Coordinate<f64> {
+impl From<&Place> for PlaceInfo {
+    fn from(place: &Place) -> Self {
+        Self {
+            id: place.id.clone(),
+            place_type: place.place_type,
+            full_name: place.full_name.clone(),
+            country_code: place.country_code.clone(),
+        }
+    }
+}
+
+impl From<MovementPoint> for Coord<f64> {
     fn from(tp: MovementPoint) -> Self {
         tp.point.0
     }
@@ -48,9 +106,19 @@ impl PointInTime for MovementPoint {
     fn point(&self) -> Point<f64> {
         self.point
     }
+
+    #[inline]
+    fn uncertainty(&self) -> Length {
+        Length::new::<meter>(self.uncertainty_m)
+    }
+
+    #[inline]
+    fn has_exact_timestamp(&self) -> bool {
+        self.is_exact_timestamp
+    }
 }
 
-#[derive(Serialize)]
+#[derive(PartialEq, Serialize, Deserialize, Clone, Debug)]
 pub struct UserMovement {
     pub user_id: u64,
     pub user_name: String,
@@ -58,6 +126,44 @@ pub struct UserMovement {
 
     /// chronologically sorted points
     pub points: Vec<MovementPoint>,
+
+    /// the number of points removed because of implausible speeds
+    #[serde(default)]
+    pub outliers_removed: usize,
+}
+
+#[cfg(test)]
+impl UserMovement {
+    /// a movement of user 1 along the equator from `(timestamp, longitude, text)` tuples
+    pub(crate) fn along_equator(points: &[(i64, f64, &str)]) -> Self {
+        use chrono::TimeZone;
+
+        Self {
+            user_id: 1,
+            user_name: String::new(),
+            user_screen_name: String::new(),
+            points: points
+                .iter()
+                .enumerate()
+                .map(|(i, (ts, x, text))| MovementPoint {
+                    tweet_id: i as u64,
+                    point: Point::new(*x, 0.0),
+                    is_exact_location: true,
+                    uncertainty_m: 0.0,
+                    is_retweet: false,
+                    timestamp: Utc.timestamp_opt(*ts, 0).unwrap(),
+                    is_exact_timestamp: false,
+                    text: text.to_string(),
+                    in_reply_to_user_id: None,
+                    lang: None,
+                    travel_speed_from_last_tweet_kmh: None,
+                    transport_mode_from_last_tweet: None,
+                    place: None,
+                })
+                .collect(),
+            outliers_removed: 0,
+        }
+    }
 }
 
 impl UserMovement {
@@ -70,38 +176,114 @@ impl UserMovement {
 
     /// expects the point to be sorted chronologically
     pub fn metrics(&self) -> Metrics {
+        self.metrics_with_options(&MetricsOptions::default())
+    }
+
+    /// expects the point to be sorted chronologically
+    pub fn metrics_with_options(&self, options: &MetricsOptions) -> Metrics {
+        Metrics::from_points(&self.points, options)
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct MetricsOptions {
+    /// the estimate of the speeds to use, see [`SpeedBound`]
+    pub speed_bound: SpeedBound,
+    pub home_work: HomeWorkOptions,
+    /// route between the points along this network. The speeds and transport modes are
+    /// computed from the length of the routes instead of the geodesic distance and ignore the
+    /// `speed_bound`.
+    pub network: Option<Arc<RoadNetwork>>,
+    pub flights: FlightOptions,
+}
+
+#[derive(Debug)]
+pub struct Metrics {
+    pub point_count: usize,
+    pub straightness_median: f64,
+    pub speeds_kmh_pc_10: f64,
+    pub speeds_kmh_pc_50: f64,
+    pub speeds_kmh_pc_80: f64,
+    pub speeds_kmh_pc_100: f64,
+    /// the number of segments without a speed because their points are too close in time,
+    /// the percentiles only cover the other segments
+    pub speeds_undefined: usize,
+    /// likelihood of the user being an automated account, see [`crate::bot`]
+    pub bot_score: f64,
+    pub home: Option<InferredLocation>,
+    pub work: Option<InferredLocation>,
+    /// the share of each transport mode in the segments with a defined speed, flights
+    /// included
+    pub mode_shares: BTreeMap<TransportMode, f64>,
+    pub flights: Vec<Flight>,
+    /// the length of the routes between the points when a network is given in the options
+    pub network_distance_km: Option<f64>,
+    /// the number of segments without a route on the network, which follow the straight line
+    /// instead
+    pub unrouted_segments: Option<usize>,
+}
+
+impl Metrics {
+    /// expects the points to be sorted chronologically
+    pub fn from_points(points: &[MovementPoint], options: &MetricsOptions) -> Self {
+        let routes = options
+            .network
+            .as_ref()
+            .map(|network| network.match_points(points));
+        Self::along_routes(points, routes.as_deref(), options)
+    }
+
+    /// the metrics with the speeds measured along `routes`, the routes between consecutive
+    /// points as returned by [`RoadNetwork::match_points`] for the network of the options.
+    ///
+    /// expects the points to be sorted chronologically
+    pub fn along_routes(
+        points: &[MovementPoint],
+        routes: Option<&[Route]>,
+        options: &MetricsOptions,
+    ) -> Self {
+        let speeds = match routes {
+            Some(routes) => route_speeds(routes, points),
+            None => points.speeds_bounded(options.speed_bound),
+        };
+        let flight_segments = points.flight_segments(&options.flights);
         let mut speeds_kmh_data = Data::new(
-            self.points
-                .speeds()
+            speeds
                 .iter()
-                .map(|s| s.get::<kilometer_per_hour>())
-                .filter(|s| !s.is_nan())
+                .zip(flight_segments.iter())
+                .filter(|(_, is_flight)| !(options.flights.exclude_from_speeds && **is_flight))
+                .filter_map(|(s, _)| s.map(|s| s.get::<kilometer_per_hour>()))
                 .collect::<Vec<_>>(),
         );
+        let mut modes = points.transport_modes_from_speeds(&speeds);
+        label_flights(&mut modes, &flight_segments);
 
-        let coords: Vec<_> = self.points.iter().map(|tp| tp.point.0).collect();
+        let coords: Vec<_> = points.iter().map(|tp| tp.point.0).collect();
 
-        Metrics {
-            point_count: self.points.len(),
+        Self {
+            point_count: points.len(),
             straightness_median: coords.straightness_chunked_median(10),
             speeds_kmh_pc_10: speeds_kmh_data.percentile(10),
             speeds_kmh_pc_50: speeds_kmh_data.percentile(50),
             speeds_kmh_pc_80: speeds_kmh_data.percentile(80),
             speeds_kmh_pc_100: speeds_kmh_data.percentile(100),
+            speeds_undefined: speeds.iter().filter(|s| s.is_none()).count(),
+            bot_score: BotScore::from_points(points).score(),
+            home: points.home(&options.home_work),
+            work: points.work(&options.home_work),
+            mode_shares: mode_shares(&modes),
+            flights: points.flights(&options.flights),
+            network_distance_km: routes.map(|routes| {
+                routes
+                    .iter()
+                    .map(|route| route.length.get::<kilometer>())
+                    .sum()
+            }),
+            unrouted_segments: routes.map(|routes| routes.iter().filter(|r| !r.routed).count()),
         }
     }
 }
 
-#[derive(Debug)]
-pub struct Metrics {
-    pub point_count: usize,
-    pub straightness_median: f64,
-    pub speeds_kmh_pc_10: f64,
-    pub speeds_kmh_pc_50: f64,
-    pub speeds_kmh_pc_80: f64,
-    pub speeds_kmh_pc_100: f64,
-}
-
 impl Metrics {
     pub fn to_vec(&self) -> Vec<f64> {
         vec![
@@ -111,6 +293,9 @@ impl Metrics {
             self.speeds_kmh_pc_50,
             self.speeds_kmh_pc_80,
             self.speeds_kmh_pc_100,
+            self.bot_score,
+            self.home.as_ref().map(|l| l.confidence).unwrap_or(0.0),
+            self.work.as_ref().map(|l| l.confidence).unwrap_or(0.0),
         ]
     }
 }
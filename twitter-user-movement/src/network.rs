@@ -0,0 +1,336 @@
+use crate::algo::speed::speed_over;
+use crate::algo::PointInTime;
+use geo::prelude::GeodesicDistance;
+use geo_types::{Coord, Geometry, LineString, Point};
+use geojson::GeoJson;
+use ordered_float::OrderedFloat;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::path::Path;
+use uom::si::f64::{Length, Velocity};
+use uom::si::length::meter;
+
+/// edge length of the cells of the grid used to find the nearest node, in degrees
+const GRID_CELL_DEG: f64 = 0.01;
+
+/// meters per degree of latitude
+const METERS_PER_DEG: f64 = 111_320.0;
+
+/// routes longer than this multiple of the geodesic distance between their nodes, plus
+/// [`MAX_DETOUR_SLACK_M`], are not searched. Such detours are unlikely to be the path taken
+/// and would make the search visit large parts of the network.
+const MAX_DETOUR_FACTOR: f64 = 3.0;
+
+/// allowance in meters for detours between nodes which are close to each other
+const MAX_DETOUR_SLACK_M: f64 = 1_000.0;
+
+/// a road graph to route between consecutive points of a movement.
+///
+/// The vertices of the LineStrings are the nodes of the graph, LineStrings sharing a vertex
+/// are connected.
+#[derive(Debug, Clone)]
+pub struct RoadNetwork {
+    nodes: Vec<Coord<f64>>,
+    /// the neighbours of each node together with the length of the edge in meters
+    edges: Vec<Vec<(usize, f64)>>,
+    /// the nodes within each cell of the grid
+    grid: HashMap<(i64, i64), Vec<usize>>,
+
+    /// points further away from the network are not routed
+    pub max_snap_distance: Length,
+}
+
+/// the path between two points
+#[derive(Debug, Clone, PartialEq)]
+pub struct Route {
+    pub line: LineString<f64>,
+    pub length: Length,
+    /// `false` for the straight line used when the points could not be routed
+    pub routed: bool,
+}
+
+impl Route {
+    fn straight(from: &Point<f64>, to: &Point<f64>) -> Self {
+        Self {
+            line: LineString::from(vec![from.0, to.0]),
+            length: Length::new::<meter>(from.geodesic_distance(to)),
+            routed: false,
+        }
+    }
+}
+
+fn grid_cell(coord: &Coord<f64>) -> (i64, i64) {
+    (
+        (coord.x / GRID_CELL_DEG).floor() as i64,
+        (coord.y / GRID_CELL_DEG).floor() as i64,
+    )
+}
+
+impl RoadNetwork {
+    pub fn new<I>(linestrings: I, max_snap_distance: Length) -> Self
+    where
+        I: IntoIterator<Item = LineString<f64>>,
+    {
+        let mut network = Self {
+            nodes: Vec::new(),
+            edges: Vec::new(),
+            grid: HashMap::new(),
+            max_snap_distance,
+        };
+        let mut node_ids: HashMap<(u64, u64), usize> = HashMap::new();
+        for linestring in linestrings {
+            let mut prev: Option<usize> = None;
+            for coord in linestring.coords() {
+                let node = *node_ids
+                    .entry((coord.x.to_bits(), coord.y.to_bits()))
+                    .or_insert_with(|| {
+                        network.nodes.push(*coord);
+                        network.edges.push(Vec::new());
+                        network
+                            .grid
+                            .entry(grid_cell(coord))
+                            .or_default()
+                            .push(network.nodes.len() - 1);
+                        network.nodes.len() - 1
+                    });
+                if let Some(prev) = prev.filter(|prev| *prev != node) {
+                    let length = Point::from(network.nodes[prev])
+                        .geodesic_distance(&Point::from(network.nodes[node]));
+                    network.edges[prev].push((node, length));
+                    network.edges[node].push((prev, length));
+                }
+                prev = Some(node);
+            }
+        }
+        network
+    }
+
+    /// load all LineStrings contained in a GeoJSON file
+    pub fn from_geojson_file<P: AsRef<Path>>(
+        path: P,
+        max_snap_distance: Length,
+    ) -> eyre::Result<Self> {
+        let geojson: GeoJson = std::fs::read_to_string(path.as_ref())?.parse()?;
+        let collection = geojson::quick_collection(&geojson)?;
+        let mut linestrings = Vec::new();
+        for geometry in collection {
+            match geometry {
+                Geometry::LineString(linestring) => linestrings.push(linestring),
+                Geometry::MultiLineString(mls) => linestrings.extend(mls.0),
+                _ => (),
+            }
+        }
+        if linestrings.is_empty() {
+            return Err(eyre::eyre!(
+                "{} does not contain any linestrings",
+                path.as_ref().display()
+            ));
+        }
+        Ok(Self::new(linestrings, max_snap_distance))
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// the node closest to `point` within the `max_snap_distance` together with its distance
+    /// in meters
+    fn nearest_node(&self, point: &Point<f64>) -> Option<(usize, f64)> {
+        let max_distance_m = self.max_snap_distance.get::<meter>();
+        let cos_lat = point.y().to_radians().cos().max(0.01);
+        let cells_x = (max_distance_m / (METERS_PER_DEG * cos_lat) / GRID_CELL_DEG).ceil() as i64;
+        let cells_y = (max_distance_m / METERS_PER_DEG / GRID_CELL_DEG).ceil() as i64;
+        let (cx, cy) = grid_cell(&point.0);
+        let mut nearest: Option<(usize, f64)> = None;
+        for x in (cx - cells_x)..=(cx + cells_x) {
+            for y in (cy - cells_y)..=(cy + cells_y) {
+                for node in self.grid.get(&(x, y)).into_iter().flatten() {
+                    let distance = point.geodesic_distance(&Point::from(self.nodes[*node]));
+                    if distance <= max_distance_m
+                        && nearest.map(|(_, d)| distance < d).unwrap_or(true)
+                    {
+                        nearest = Some((*node, distance));
+                    }
+                }
+            }
+        }
+        nearest
+    }
+
+    /// the shortest path between the nodes as list of nodes together with its length in meters.
+    ///
+    /// An A* search using the geodesic distance to the target as estimate of the remaining
+    /// length. `None` when the nodes are not connected within the [`MAX_DETOUR_FACTOR`].
+    fn shortest_path(&self, from: usize, to: usize) -> Option<(Vec<usize>, f64)> {
+        let target = Point::from(self.nodes[to]);
+        let remaining = |node: usize| Point::from(self.nodes[node]).geodesic_distance(&target);
+        let max_length = remaining(from) * MAX_DETOUR_FACTOR + MAX_DETOUR_SLACK_M;
+
+        let mut distances = HashMap::from([(from, 0.0)]);
+        let mut predecessors: HashMap<usize, usize> = HashMap::new();
+        let mut visited = HashSet::new();
+        let mut queue = BinaryHeap::from([Reverse((OrderedFloat(remaining(from)), from))]);
+        while let Some(Reverse((_, node))) = queue.pop() {
+            let distance = distances[&node];
+            if node == to {
+                let mut path = vec![to];
+                while let Some(pred) = predecessors.get(path.last()?) {
+                    path.push(*pred);
+                }
+                path.reverse();
+                return Some((path, distance));
+            }
+            if !visited.insert(node) {
+                continue;
+            }
+            for (neighbour, length) in self.edges[node].iter() {
+                let candidate = distance + length;
+                let estimate = candidate + remaining(*neighbour);
+                if estimate <= max_length
+                    && distances
+                        .get(neighbour)
+                        .map(|d| candidate < *d)
+                        .unwrap_or(true)
+                {
+                    distances.insert(*neighbour, candidate);
+                    predecessors.insert(*neighbour, node);
+                    queue.push(Reverse((OrderedFloat(estimate), *neighbour)));
+                }
+            }
+        }
+        None
+    }
+
+    /// route between the points along the network. The route includes the straight
+    /// connections of the points to their nearest nodes.
+    ///
+    /// Falls back to the straight line when one of the points is further away from the
+    /// network than `max_snap_distance` or the network does not connect them without a
+    /// large detour.
+    pub fn route(&self, from: &Point<f64>, to: &Point<f64>) -> Route {
+        let snapped = self.nearest_node(from).zip(self.nearest_node(to));
+        let ((from_node, from_distance), (to_node, to_distance)) = match snapped {
+            Some(snapped) => snapped,
+            None => return Route::straight(from, to),
+        };
+        match self.shortest_path(from_node, to_node) {
+            Some((path, length)) => {
+                let mut coords = Vec::with_capacity(path.len() + 2);
+                coords.push(from.0);
+                coords.extend(path.iter().map(|node| self.nodes[*node]));
+                coords.push(to.0);
+                coords.dedup();
+                Route {
+                    line: LineString::from(coords),
+                    length: Length::new::<meter>(from_distance + length + to_distance),
+                    routed: true,
+                }
+            }
+            None => Route::straight(from, to),
+        }
+    }
+
+    /// the routes between each pair of consecutive points
+    pub fn match_points<PIT: PointInTime>(&self, points: &[PIT]) -> Vec<Route> {
+        points
+            .windows(2)
+            .map(|window| self.route(&window[0].point(), &window[1].point()))
+            .collect()
+    }
+}
+
+/// the speed along each route between consecutive points, see [`speed_over`]
+pub fn route_speeds<PIT: PointInTime>(routes: &[Route], points: &[PIT]) -> Vec<Option<Velocity>> {
+    routes
+        .iter()
+        .zip(points.windows(2))
+        .map(|(route, window)| speed_over(route.length, &window[0], &window[1]))
+        .collect()
+}
+
+/// the routes joined into one LineString
+pub fn join_routes(routes: &[Route]) -> LineString<f64> {
+    let mut coords: Vec<Coord<f64>> = Vec::new();
+    for route in routes {
+        coords.extend(route.line.coords());
+        coords.dedup();
+    }
+    LineString::from(coords)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{join_routes, RoadNetwork};
+    use geo_types::{LineString, Point};
+    use uom::si::f64::Length;
+    use uom::si::length::meter;
+
+    fn network() -> RoadNetwork {
+        // a detour around the direct connection of (0,0) and (0.1,0)
+        RoadNetwork::new(
+            vec![
+                LineString::from(vec![(0.0, 0.0), (0.0, 0.05)]),
+                LineString::from(vec![(0.0, 0.05), (0.1, 0.05), (0.1, 0.0)]),
+                LineString::from(vec![(1.0, 1.0), (1.1, 1.0)]),
+            ],
+            Length::new::<meter>(500.0),
+        )
+    }
+
+    #[test]
+    fn route_along_network() {
+        let network = network();
+        assert_eq!(network.node_count(), 6);
+
+        let route = network.route(&Point::new(0.001, 0.0), &Point::new(0.1, 0.001));
+        assert!(route.routed);
+        assert_eq!(
+            route.line,
+            LineString::from(vec![
+                (0.001, 0.0),
+                (0.0, 0.0),
+                (0.0, 0.05),
+                (0.1, 0.05),
+                (0.1, 0.0),
+                (0.1, 0.001)
+            ])
+        );
+        // about 22.2 km along the network and 0.2 km to snap the points
+        assert_eq!((route.length.get::<meter>() / 100.0).round(), 224.0);
+    }
+
+    #[test]
+    fn fall_back_to_straight_line() {
+        let network = network();
+        // too far from the network
+        let route = network.route(&Point::new(0.05, 0.02), &Point::new(0.1, 0.0));
+        assert!(!route.routed);
+        assert_eq!(route.line.0.len(), 2);
+
+        // not connected
+        let route = network.route(&Point::new(0.0, 0.0), &Point::new(1.0, 1.0));
+        assert!(!route.routed);
+
+        // a detour of five times the direct distance
+        let detour = RoadNetwork::new(
+            vec![LineString::from(vec![
+                (0.0, 0.0),
+                (0.0, 0.2),
+                (0.1, 0.2),
+                (0.1, 0.0),
+            ])],
+            Length::new::<meter>(500.0),
+        );
+        let route = detour.route(&Point::new(0.0, 0.0), &Point::new(0.1, 0.0));
+        assert!(!route.routed);
+
+        let routes = vec![
+            network.route(&Point::new(0.0, 0.0), &Point::new(0.0, 0.05)),
+            network.route(&Point::new(0.0, 0.05), &Point::new(0.1, 0.05)),
+        ];
+        assert_eq!(
+            join_routes(&routes),
+            LineString::from(vec![(0.0, 0.0), (0.0, 0.05), (0.1, 0.05)])
+        );
+    }
+}
@@ -0,0 +1,911 @@
+use crate::algo::angle::angle_radians;
+use crate::algo::flights::{label_flights, FlightOptions};
+use crate::algo::segmentation::SegmentationOptions;
+use crate::algo::speed::{distance_bounded, speed_over, SpeedBound};
+use crate::algo::staypoints::{SignificantPlace, StayPoint, StayPointOptions};
+use crate::algo::transport_mode::TransportMode;
+use crate::algo::{Flights, Segmentation, Speed, StayPoints, TransportModes};
+use crate::ingest::Movements;
+use crate::model::{Metrics, MetricsOptions, MovementPoint, UserMovement};
+use crate::network::{join_routes, route_speeds};
+use geo::algorithm::bearing::Bearing;
+use geo_types::{Coord, LineString, Point};
+use geojson::{Feature, Value};
+use ordered_float::OrderedFloat;
+use serde::de::{MapAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use serde_json::{to_value, Map};
+use std::fmt;
+use std::io::{Read, Write};
+use uom::si::f64::Velocity;
+use uom::si::length::kilometer;
+use uom::si::velocity::kilometer_per_hour;
+
+/// writes the movements of users incrementally, so they do not need to be kept in
+/// memory all at once.
+pub trait MovementsWriter {
+    fn write_movements(&mut self, user_movements: Movements) -> eyre::Result<()>;
+
+    /// complete the output after all movements have been written
+    fn finish(&mut self) -> eyre::Result<()>;
+}
+
+/// writes the features of a GeoJSON FeatureCollection one by one
+pub struct FeatureCollectionWriter<W: Write> {
+    writer: W,
+    features_written: usize,
+}
+
+impl<W: Write> FeatureCollectionWriter<W> {
+    /// `metadata` is written as foreign member of the FeatureCollection when not empty
+    pub fn new(mut writer: W, metadata: Map<String, serde_json::Value>) -> eyre::Result<Self> {
+        writer.write_all(b"{")?;
+        if !metadata.is_empty() {
+            writer.write_all(br#""metadata":"#)?;
+            serde_json::to_writer(&mut writer, &metadata)?;
+            writer.write_all(b",")?;
+        }
+        writer.write_all(br#""features":["#)?;
+        Ok(Self {
+            writer,
+            features_written: 0,
+        })
+    }
+
+    pub fn write_feature(&mut self, feature: &Feature) -> eyre::Result<()> {
+        if self.features_written > 0 {
+            self.writer.write_all(b",")?;
+        }
+        serde_json::to_writer(&mut self.writer, feature)?;
+        self.features_written += 1;
+        Ok(())
+    }
+
+    pub fn finish(&mut self) -> eyre::Result<()> {
+        self.writer.write_all(br#"],"type":"FeatureCollection"}"#)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// writes a GeoJSON FeatureCollection containing a LineString for each user, for each
+/// trip of each user or for each segment between consecutive points
+pub struct GeoJsonWriter<W: Write> {
+    writer: FeatureCollectionWriter<W>,
+    metrics_options: MetricsOptions,
+    segmentation: Option<SegmentationOptions>,
+    segments: bool,
+}
+
+impl<W: Write> GeoJsonWriter<W> {
+    /// `metadata` is written as foreign member of the FeatureCollection when not empty
+    pub fn new(
+        writer: W,
+        metrics_options: MetricsOptions,
+        metadata: Map<String, serde_json::Value>,
+    ) -> eyre::Result<Self> {
+        Ok(Self {
+            writer: FeatureCollectionWriter::new(writer, metadata)?,
+            metrics_options,
+            segmentation: None,
+            segments: false,
+        })
+    }
+
+    /// split the movements into trips and write a feature for each trip
+    pub fn with_trips(mut self, segmentation: SegmentationOptions) -> Self {
+        self.segmentation = Some(segmentation);
+        self
+    }
+
+    /// write a feature for each segment between consecutive points instead of each user
+    pub fn with_segments(mut self) -> Self {
+        self.segments = true;
+        self
+    }
+}
+
+impl<W: Write> MovementsWriter for GeoJsonWriter<W> {
+    fn write_movements(&mut self, user_movements: Movements) -> eyre::Result<()> {
+        for (_, user_movement) in user_movements {
+            let features = if self.segments {
+                segment_features(user_movement, &self.metrics_options)?
+            } else {
+                match self.segmentation.as_ref() {
+                    Some(segmentation) => {
+                        trip_features(user_movement, segmentation, &self.metrics_options)?
+                    }
+                    None => vec![user_feature(user_movement, &self.metrics_options)?],
+                }
+            };
+            for feature in features.iter() {
+                self.writer.write_feature(feature)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> eyre::Result<()> {
+        self.writer.finish()
+    }
+}
+
+/// writes a GeoJSON FeatureCollection containing a Point for each significant place of each
+/// user, or for each stay point
+pub struct StayPointWriter<W: Write> {
+    writer: FeatureCollectionWriter<W>,
+    options: StayPointOptions,
+    places: bool,
+}
+
+impl<W: Write> StayPointWriter<W> {
+    /// write the significant places when `places` is set, otherwise the stay points
+    pub fn new(
+        writer: W,
+        options: StayPointOptions,
+        places: bool,
+        metadata: Map<String, serde_json::Value>,
+    ) -> eyre::Result<Self> {
+        Ok(Self {
+            writer: FeatureCollectionWriter::new(writer, metadata)?,
+            options,
+            places,
+        })
+    }
+}
+
+impl<W: Write> MovementsWriter for StayPointWriter<W> {
+    fn write_movements(&mut self, user_movements: Movements) -> eyre::Result<()> {
+        for (_, user_movement) in user_movements {
+            let mut user_props = Map::new();
+            user_props.insert("user_id".to_string(), to_value(user_movement.user_id)?);
+            user_props.insert(
+                "user_screen_name".to_string(),
+                to_value(&user_movement.user_screen_name)?,
+            );
+
+            let features = if self.places {
+                user_movement
+                    .points
+                    .significant_places(&self.options)
+                    .iter()
+                    .enumerate()
+                    .map(|(rank, place)| place_feature(place, rank, user_props.clone()))
+                    .collect::<eyre::Result<Vec<_>>>()?
+            } else {
+                user_movement
+                    .points
+                    .stay_points(&self.options)
+                    .iter()
+                    .map(|stay_point| stay_point_feature(stay_point, user_props.clone()))
+                    .collect::<eyre::Result<Vec<_>>>()?
+            };
+            for feature in features.iter() {
+                self.writer.write_feature(feature)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> eyre::Result<()> {
+        self.writer.finish()
+    }
+}
+
+fn point_feature(point: &Point<f64>, props: Map<String, serde_json::Value>) -> Feature {
+    Feature {
+        bbox: None,
+        geometry: Some(geojson::Geometry::new(Value::from(point))),
+        id: None,
+        properties: Some(props),
+        foreign_members: None,
+    }
+}
+
+fn stay_point_feature(
+    stay_point: &StayPoint,
+    mut props: Map<String, serde_json::Value>,
+) -> eyre::Result<Feature> {
+    props.insert("arrival".to_string(), to_value(stay_point.arrival)?);
+    props.insert("departure".to_string(), to_value(stay_point.departure)?);
+    props.insert(
+        "duration_s".to_string(),
+        to_value(stay_point.duration().num_seconds())?,
+    );
+    props.insert("point_count".to_string(), to_value(stay_point.point_count)?);
+    Ok(point_feature(&stay_point.point, props))
+}
+
+/// `rank` is the index of the place when ordered by descending dwell time
+fn place_feature(
+    place: &SignificantPlace,
+    rank: usize,
+    mut props: Map<String, serde_json::Value>,
+) -> eyre::Result<Feature> {
+    props.insert("rank".to_string(), to_value(rank)?);
+    props.insert("visit_count".to_string(), to_value(place.visit_count())?);
+    props.insert(
+        "dwell_time_s".to_string(),
+        to_value(place.dwell_time().num_seconds())?,
+    );
+    props.insert("point_count".to_string(), to_value(place.point_count())?);
+    if let (Some(first), Some(last)) = (place.stay_points.first(), place.stay_points.last()) {
+        props.insert("first_arrival".to_string(), to_value(first.arrival)?);
+        props.insert("last_departure".to_string(), to_value(last.departure)?);
+    }
+    Ok(point_feature(&place.point, props))
+}
+
+/// the maximum of the speeds in km/h, leaving out the flight segments when `exclude_flights`
+/// is set
+fn max_speed_kmh(
+    speeds: &[Option<Velocity>],
+    flight_segments: &[bool],
+    exclude_flights: bool,
+) -> Option<f64> {
+    speeds
+        .iter()
+        .zip(flight_segments)
+        .filter(|(_, is_flight)| !(exclude_flights && **is_flight))
+        .filter_map(|(speed, _)| *speed)
+        .map(|v| OrderedFloat(v.get::<kilometer_per_hour>()))
+        .max()
+        .map(|v| v.0)
+}
+
+/// the LineString along `points` together with the properties shared by the features of
+/// users and trips
+fn movement_feature(
+    user_id: u64,
+    user_name: &str,
+    user_screen_name: &str,
+    points: &[MovementPoint],
+    metrics_options: &MetricsOptions,
+) -> eyre::Result<Feature> {
+    let routes = metrics_options
+        .network
+        .as_ref()
+        .map(|network| network.match_points(points));
+    let metrics = Metrics::along_routes(points, routes.as_deref(), metrics_options);
+
+    let linestring = match routes.as_ref() {
+        Some(routes) if !routes.is_empty() => join_routes(routes),
+        _ => {
+            let coordinates: Vec<Coord<f64>> = points.iter().map(|tp| tp.point.0).collect();
+            LineString::from(coordinates)
+        }
+    };
+
+    let mut props = Map::new();
+    props.insert("sp_pc_10".to_string(), to_value(metrics.speeds_kmh_pc_10)?);
+    props.insert("sp_pc_50".to_string(), to_value(metrics.speeds_kmh_pc_50)?);
+    props.insert("sp_pc_80".to_string(), to_value(metrics.speeds_kmh_pc_80)?);
+    props.insert(
+        "sp_pc_100".to_string(),
+        to_value(metrics.speeds_kmh_pc_100)?,
+    );
+    props.insert(
+        "sp_undefined".to_string(),
+        to_value(metrics.speeds_undefined)?,
+    );
+    props.insert(
+        "straightness_median".to_string(),
+        to_value(metrics.straightness_median)?,
+    );
+    props.insert("bot_score".to_string(), to_value(metrics.bot_score)?);
+    for mode in TransportMode::ALL {
+        props.insert(
+            format!("mode_share_{}", mode),
+            to_value(metrics.mode_shares.get(&mode).copied().unwrap_or(0.0))?,
+        );
+    }
+    for (name, location) in [("home", &metrics.home), ("work", &metrics.work)] {
+        let location = location.as_ref();
+        props.insert(
+            format!("{}_x", name),
+            to_value(location.map(|l| l.point.x()))?,
+        );
+        props.insert(
+            format!("{}_y", name),
+            to_value(location.map(|l| l.point.y()))?,
+        );
+        props.insert(
+            format!("{}_confidence", name),
+            to_value(location.map(|l| l.confidence))?,
+        );
+        props.insert(
+            format!("{}_tweet_count", name),
+            to_value(location.map(|l| l.point_count).unwrap_or(0))?,
+        );
+    }
+
+    // the speeds of the metrics are computed along the routes when there are any
+    let flight_segments = points.flight_segments(&metrics_options.flights);
+    let nominal_speeds = match routes.as_ref() {
+        Some(routes) => route_speeds(routes, points),
+        None => points.speeds_bounded(metrics_options.speed_bound),
+    };
+    for (name, speeds) in [
+        ("max_speed_kmh", nominal_speeds),
+        (
+            "max_speed_lower_kmh",
+            points.speeds_bounded(SpeedBound::Lower),
+        ),
+        (
+            "max_speed_upper_kmh",
+            points.speeds_bounded(SpeedBound::Upper),
+        ),
+    ] {
+        props.insert(
+            name.to_string(),
+            to_value(max_speed_kmh(
+                &speeds,
+                &flight_segments,
+                metrics_options.flights.exclude_from_speeds,
+            ))?,
+        );
+    }
+    if metrics_options.network.is_some() {
+        props.insert(
+            "network_distance_km".to_string(),
+            to_value(metrics.network_distance_km)?,
+        );
+        props.insert(
+            "unrouted_segments".to_string(),
+            to_value(metrics.unrouted_segments)?,
+        );
+    }
+    props.insert("flight_count".to_string(), to_value(metrics.flights.len())?);
+    props.insert("flights".to_string(), to_value(&metrics.flights)?);
+    props.insert("user_name".to_string(), to_value(user_name)?);
+    props.insert("user_id".to_string(), to_value(user_id)?);
+    props.insert("user_screen_name".to_string(), to_value(user_screen_name)?);
+
+    Ok(Feature {
+        bbox: None,
+        geometry: Some(geojson::Geometry::new(Value::from(&linestring))),
+        id: None,
+        properties: Some(props),
+        foreign_members: None,
+    })
+}
+
+/// the LineString of the movement of a user together with the metrics as properties.
+///
+/// With a network in the `metrics_options` the LineString follows the routes between the
+/// points.
+pub fn user_feature(
+    user_movement: UserMovement,
+    metrics_options: &MetricsOptions,
+) -> eyre::Result<Feature> {
+    let mut feature = movement_feature(
+        user_movement.user_id,
+        &user_movement.user_name,
+        &user_movement.user_screen_name,
+        &user_movement.points,
+        metrics_options,
+    )?;
+    if let Some(props) = feature.properties.as_mut() {
+        props.insert(
+            "outliers_removed".to_string(),
+            to_value(user_movement.outliers_removed)?,
+        );
+    }
+    Ok(feature)
+}
+
+/// a feature for each trip of the user with at least two points. The properties contain the
+/// metrics of the trip together with its index among these trips, start and end.
+///
+/// The removed outliers are only counted per user, so they are left out of the trips.
+pub fn trip_features(
+    user_movement: UserMovement,
+    segmentation: &SegmentationOptions,
+    metrics_options: &MetricsOptions,
+) -> eyre::Result<Vec<Feature>> {
+    let mut features = Vec::new();
+    let trips = user_movement.points.trips(segmentation);
+    for (trip_index, trip) in trips.iter().filter(|trip| trip.len() >= 2).enumerate() {
+        let (start, end) = (trip[0].timestamp, trip[trip.len() - 1].timestamp);
+        let mut feature = movement_feature(
+            user_movement.user_id,
+            &user_movement.user_name,
+            &user_movement.user_screen_name,
+            trip,
+            metrics_options,
+        )?;
+        if let Some(props) = feature.properties.as_mut() {
+            props.insert("trip_index".to_string(), to_value(trip_index)?);
+            props.insert("trip_start".to_string(), to_value(start)?);
+            props.insert("trip_end".to_string(), to_value(end)?);
+            props.insert(
+                "trip_duration_s".to_string(),
+                to_value((end - start).num_seconds())?,
+            );
+        }
+        features.push(feature);
+    }
+    Ok(features)
+}
+
+/// a feature for each segment between consecutive points of the user. The properties describe
+/// the movement along the segment, the turning angle is the change of direction at its start.
+///
+/// The distances follow the `speed_bound` of the `metrics_options` like the speeds. With a
+/// network the segments follow the routes and their distances and speeds are measured along
+/// the routes.
+pub fn segment_features(
+    user_movement: UserMovement,
+    metrics_options: &MetricsOptions,
+) -> eyre::Result<Vec<Feature>> {
+    let points = &user_movement.points;
+    let routes = metrics_options
+        .network
+        .as_ref()
+        .map(|network| network.match_points(points));
+    // the speeds and transport modes are derived from the same distances, which follow the
+    // routes or the speed bound
+    let distances = points
+        .windows(2)
+        .enumerate()
+        .map(|(idx, window)| match routes.as_ref() {
+            Some(routes) => routes[idx].length,
+            None => distance_bounded(&window[0], &window[1], metrics_options.speed_bound),
+        })
+        .collect::<Vec<_>>();
+    let speeds = distances
+        .iter()
+        .zip(points.windows(2))
+        .map(|(distance, window)| speed_over(*distance, &window[0], &window[1]))
+        .collect::<Vec<_>>();
+    let flight_segments = points.flight_segments(&metrics_options.flights);
+    let mut modes = points.transport_modes_from_speeds(&speeds);
+    label_flights(&mut modes, &flight_segments);
+    let coords = points.iter().map(|mp| mp.point.0).collect::<Vec<_>>();
+
+    let mut features = Vec::with_capacity(points.len().saturating_sub(1));
+    for (idx, window) in points.windows(2).enumerate() {
+        let (from, to) = (&window[0], &window[1]);
+        let route = routes.as_ref().map(|routes| &routes[idx]);
+        let linestring = match route {
+            Some(route) => route.line.clone(),
+            None => LineString::from(vec![from.point.0, to.point.0]),
+        };
+        let (distance, speed) = (distances[idx], speeds[idx]);
+        let turning_angle = if idx > 0 {
+            Some(angle_radians(&[coords[idx - 1], coords[idx], coords[idx + 1]]).to_degrees())
+                .filter(|angle| !angle.is_nan())
+        } else {
+            None
+        };
+
+        let mut props = Map::new();
+        props.insert("user_id".to_string(), to_value(user_movement.user_id)?);
+        props.insert(
+            "user_screen_name".to_string(),
+            to_value(&user_movement.user_screen_name)?,
+        );
+        props.insert("segment_index".to_string(), to_value(idx)?);
+        props.insert("start".to_string(), to_value(from.timestamp)?);
+        props.insert("end".to_string(), to_value(to.timestamp)?);
+        props.insert(
+            "duration_s".to_string(),
+            to_value((to.timestamp - from.timestamp).num_milliseconds() as f64 / 1000.0)?,
+        );
+        props.insert(
+            "distance_km".to_string(),
+            to_value(distance.get::<kilometer>())?,
+        );
+        props.insert(
+            "speed_kmh".to_string(),
+            to_value(speed.map(|v| v.get::<kilometer_per_hour>()))?,
+        );
+        props.insert(
+            "bearing_deg".to_string(),
+            // clockwise from north between 0 and 360 degrees
+            to_value(Some(from.point.bearing(to.point).rem_euclid(360.0)).filter(|b| !b.is_nan()))?,
+        );
+        props.insert("turning_angle_deg".to_string(), to_value(turning_angle)?);
+        props.insert(
+            "transport_mode".to_string(),
+            to_value(modes[idx].map(|estimate| estimate.mode))?,
+        );
+        props.insert(
+            "mode_confidence".to_string(),
+            to_value(modes[idx].map(|estimate| estimate.confidence))?,
+        );
+        props.insert("is_flight".to_string(), to_value(flight_segments[idx])?);
+        if let Some(route) = route {
+            props.insert("routed".to_string(), to_value(route.routed)?);
+        }
+
+        features.push(Feature {
+            bbox: None,
+            geometry: Some(geojson::Geometry::new(Value::from(&linestring))),
+            id: None,
+            properties: Some(props),
+            foreign_members: None,
+        });
+    }
+    Ok(features)
+}
+
+/// the key of the metadata in the JSON object written by a [`MovementJsonWriter`]. The other
+/// keys are user ids.
+const METADATA_KEY: &str = "metadata";
+
+/// the metadata keys of the settings the travel speeds and transport modes are computed with
+const SPEED_SETTINGS: [&str; 2] = ["speed_bound", "flights"];
+
+/// writes a JSON object containing the movement of each user keyed by the user id.
+///
+/// The travel speeds and transport modes of all points are computed with the settings of the
+/// writer, so movements read from an earlier output never keep values computed with other
+/// settings.
+pub struct MovementJsonWriter<W: Write> {
+    writer: W,
+    speed_bound: SpeedBound,
+    flights: FlightOptions,
+    metadata: Map<String, serde_json::Value>,
+    users_written: usize,
+}
+
+impl<W: Write> MovementJsonWriter<W> {
+    /// `metadata` is written under the `"metadata"` key together with the speed settings
+    pub fn new(
+        mut writer: W,
+        speed_bound: SpeedBound,
+        metadata: Map<String, serde_json::Value>,
+    ) -> eyre::Result<Self> {
+        writer.write_all(b"{")?;
+        Ok(Self {
+            writer,
+            speed_bound,
+            flights: FlightOptions::default(),
+            metadata,
+            users_written: 0,
+        })
+    }
+
+    /// the thresholds to label segments as flights
+    pub fn with_flights(mut self, flights: FlightOptions) -> Self {
+        self.flights = flights;
+        self
+    }
+
+    /// the metadata written by [`MovementsWriter::finish`]
+    pub fn metadata(&self) -> eyre::Result<Map<String, serde_json::Value>> {
+        let mut metadata = self.metadata.clone();
+        metadata.insert(
+            SPEED_SETTINGS[0].to_string(),
+            to_value(self.speed_bound.to_string())?,
+        );
+        metadata.insert(SPEED_SETTINGS[1].to_string(), to_value(&self.flights)?);
+        Ok(metadata)
+    }
+
+    /// true when the travel speeds and transport modes of movements with this metadata have
+    /// been computed with the same settings as those of this writer
+    pub fn same_speed_settings(
+        &self,
+        metadata: Option<&Map<String, serde_json::Value>>,
+    ) -> eyre::Result<bool> {
+        let own = self.metadata()?;
+        Ok(metadata
+            .map(|metadata| {
+                SPEED_SETTINGS
+                    .iter()
+                    .all(|key| metadata.get(*key) == own.get(*key))
+            })
+            .unwrap_or(false))
+    }
+}
+
+impl<W: Write> MovementsWriter for MovementJsonWriter<W> {
+    fn write_movements(&mut self, user_movements: Movements) -> eyre::Result<()> {
+        for (user_id, mut user) in user_movements {
+            // enrich with travel speeds and transport modes first
+            let speeds = user.points.speeds_bounded(self.speed_bound);
+            let mut modes = user.points.transport_modes_from_speeds(&speeds);
+            label_flights(&mut modes, &user.points.flight_segments(&self.flights));
+            for ((point, speed), mode) in user.points.iter_mut().skip(1).zip(speeds).zip(modes) {
+                point.travel_speed_from_last_tweet_kmh =
+                    speed.map(|v| v.get::<kilometer_per_hour>());
+                point.transport_mode_from_last_tweet = mode;
+            }
+
+            if self.users_written > 0 {
+                self.writer.write_all(b",")?;
+            }
+            serde_json::to_writer(&mut self.writer, &user_id.to_string())?;
+            self.writer.write_all(b":")?;
+            serde_json::to_writer(&mut self.writer, &user)?;
+            self.users_written += 1;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> eyre::Result<()> {
+        if self.users_written > 0 {
+            self.writer.write_all(b",")?;
+        }
+        let metadata = self.metadata()?;
+        serde_json::to_writer(&mut self.writer, METADATA_KEY)?;
+        self.writer.write_all(b":")?;
+        serde_json::to_writer(&mut self.writer, &metadata)?;
+        self.writer.write_all(b"}\n")?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// the contents of a file written by a [`MovementJsonWriter`]
+#[derive(Debug, Default)]
+pub struct MovementJson {
+    pub movements: Movements,
+    /// `None` for files written before the metadata was added
+    pub metadata: Option<Map<String, serde_json::Value>>,
+}
+
+impl<'de> Deserialize<'de> for MovementJson {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct MovementJsonVisitor;
+
+        impl<'de> Visitor<'de> for MovementJsonVisitor {
+            type Value = MovementJson;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("an object of movements keyed by user id")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut movement_json = MovementJson::default();
+                while let Some(key) = map.next_key::<String>()? {
+                    if key == METADATA_KEY {
+                        movement_json.metadata = Some(map.next_value()?);
+                    } else {
+                        let user_id = key.parse().map_err(|_| {
+                            serde::de::Error::custom(format!("invalid user id \"{}\"", key))
+                        })?;
+                        movement_json.movements.insert(user_id, map.next_value()?);
+                    }
+                }
+                Ok(movement_json)
+            }
+        }
+
+        deserializer.deserialize_map(MovementJsonVisitor)
+    }
+}
+
+/// read movements written by a [`MovementJsonWriter`]
+pub fn read_movement_json<R: Read>(reader: R) -> eyre::Result<MovementJson> {
+    Ok(serde_json::from_reader(reader)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        read_movement_json, segment_features, trip_features, MovementJson, MovementJsonWriter,
+        MovementsWriter,
+    };
+    use crate::algo::segmentation::SegmentationOptions;
+    use crate::algo::speed::SpeedBound;
+    use crate::algo::transport_mode::TransportMode;
+    use crate::ingest::{update_movements, Movements};
+    use crate::model::{MetricsOptions, UserMovement};
+    use crate::network::RoadNetwork;
+    use chrono::Duration;
+    use geo_types::{LineString, Point};
+    use serde_json::{to_value, Map, Value};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use uom::si::f64::Length;
+    use uom::si::length::meter;
+
+    fn properties(
+        user_movement: UserMovement,
+        metrics_options: &MetricsOptions,
+    ) -> Vec<Map<String, Value>> {
+        segment_features(user_movement, metrics_options)
+            .unwrap()
+            .into_iter()
+            .map(|feature| feature.properties.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn segment_properties() {
+        // west along the equator, staying at the same point, east again and a short turn to
+        // the north half a second later
+        let mut user_movement = UserMovement::along_equator(&[
+            (0, 0.1, ""),
+            (3600, 0.0, ""),
+            (7200, 0.0, ""),
+            (10800, 0.1, ""),
+            (10800, 0.1, ""),
+        ]);
+        user_movement.points[4].point = Point::new(0.1, 0.001);
+        user_movement.points[4].timestamp += Duration::milliseconds(500);
+        for point in user_movement.points.iter_mut() {
+            point.uncertainty_m = 1000.0;
+        }
+        let metrics_options = MetricsOptions {
+            speed_bound: SpeedBound::Lower,
+            ..Default::default()
+        };
+        let props = properties(user_movement, &metrics_options);
+        assert_eq!(props.len(), 4);
+
+        let f64_prop = |idx: usize, key: &str| props[idx][key].as_f64();
+        assert_eq!(
+            (0..4)
+                .map(|idx| f64_prop(idx, "duration_s").unwrap())
+                .collect::<Vec<_>>(),
+            vec![3600.0, 3600.0, 3600.0, 0.5]
+        );
+
+        // no turning angle for the first segment and around the duplicate point
+        assert_eq!(f64_prop(0, "turning_angle_deg"), None);
+        assert_eq!(f64_prop(1, "turning_angle_deg"), None);
+        assert_eq!(f64_prop(2, "turning_angle_deg"), None);
+        assert_eq!(f64_prop(3, "turning_angle_deg").unwrap().round(), 90.0);
+
+        for idx in 0..4 {
+            let bearing = f64_prop(idx, "bearing_deg").unwrap();
+            assert!((0.0..360.0).contains(&bearing));
+        }
+        assert_eq!(f64_prop(0, "bearing_deg").unwrap().round(), 270.0);
+        assert_eq!(f64_prop(2, "bearing_deg").unwrap().round(), 90.0);
+
+        // the distances follow the lower bound just like the speeds
+        assert_eq!((f64_prop(0, "distance_km").unwrap() * 100.0).round(), 913.0);
+        for idx in 0..3 {
+            let distance_km = f64_prop(idx, "distance_km").unwrap();
+            let speed_kmh = f64_prop(idx, "speed_kmh").unwrap();
+            assert!((distance_km - speed_kmh).abs() < 1e-9);
+        }
+
+        // undefined below the minimum time between the points
+        assert_eq!(props[3]["speed_kmh"], Value::Null);
+        assert!(props.iter().all(|p| !p.contains_key("routed")));
+    }
+
+    #[test]
+    fn routed_segments() {
+        let user_movement =
+            UserMovement::along_equator(&[(0, 0.0, ""), (3600, 0.1, ""), (7200, 0.5, "")]);
+        let metrics_options = MetricsOptions {
+            network: Some(Arc::new(RoadNetwork::new(
+                vec![LineString::from(vec![(0.0, 0.0), (0.1, 0.0)])],
+                Length::new::<meter>(500.0),
+            ))),
+            ..Default::default()
+        };
+        let props = properties(user_movement, &metrics_options);
+        assert_eq!(props[0]["routed"], Value::Bool(true));
+        assert_eq!(props[1]["routed"], Value::Bool(false));
+    }
+
+    #[test]
+    fn trips_without_outlier_counts() {
+        // the single point in between is no trip and does not take up a trip index
+        let mut user_movement = UserMovement::along_equator(&[
+            (0, 0.0, ""),
+            (3600, 0.1, ""),
+            (43200, 0.15, ""),
+            (86400, 0.2, ""),
+            (90000, 0.3, ""),
+        ]);
+        user_movement.outliers_removed = 3;
+        let features = trip_features(
+            user_movement,
+            &SegmentationOptions::default(),
+            &MetricsOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(features.len(), 2);
+        for (trip_index, feature) in features.iter().enumerate() {
+            let props = feature.properties.as_ref().unwrap();
+            assert_eq!(props["trip_index"], to_value(trip_index).unwrap());
+            assert!(!props.contains_key("outliers_removed"));
+            assert_eq!(props["user_id"], to_value(1).unwrap());
+        }
+    }
+
+    #[test]
+    fn transport_modes_along_routes() {
+        // 11 km in half an hour, but the road makes a detour of twice the distance
+        let user_movement = UserMovement::along_equator(&[(0, 0.0, ""), (1800, 0.1, "")]);
+        let network = RoadNetwork::new(
+            vec![LineString::from(vec![
+                (0.0, 0.0),
+                (0.0, 0.05),
+                (0.1, 0.05),
+                (0.1, 0.0),
+            ])],
+            Length::new::<meter>(500.0),
+        );
+        let routed_options = MetricsOptions {
+            network: Some(Arc::new(network)),
+            ..Default::default()
+        };
+
+        for (metrics_options, mode, network_distance_km) in [
+            (MetricsOptions::default(), TransportMode::Bike, None),
+            (routed_options, TransportMode::Car, Some(22.0)),
+        ] {
+            let props = properties(user_movement.clone(), &metrics_options);
+            assert_eq!(props[0]["transport_mode"], to_value(mode).unwrap());
+            let metrics = user_movement.metrics_with_options(&metrics_options);
+            assert_eq!(metrics.mode_shares.keys().collect::<Vec<_>>(), vec![&mode]);
+            assert_eq!(
+                metrics.network_distance_km.map(f64::round),
+                network_distance_km
+            );
+            assert_eq!(metrics.unrouted_segments, network_distance_km.map(|_| 0));
+        }
+    }
+
+    #[test]
+    fn update_recomputes_speeds() {
+        let write = |movements: Movements| {
+            let mut buf = Vec::new();
+            let mut writer =
+                MovementJsonWriter::new(&mut buf, SpeedBound::Nominal, Map::new()).unwrap();
+            writer.write_movements(movements).unwrap();
+            writer.finish().unwrap();
+            read_movement_json(buf.as_slice()).unwrap()
+        };
+        let speeds = |movement_json: &MovementJson| {
+            movement_json.movements[&1]
+                .points
+                .iter()
+                .map(|mp| mp.travel_speed_from_last_tweet_kmh.map(f64::round))
+                .collect::<Vec<_>>()
+        };
+
+        // ~11 km/h along the equator
+        let existing = write(HashMap::from([(
+            1,
+            UserMovement::along_equator(&[(3600, 0.1, ""), (7200, 0.2, "")]),
+        )]));
+        assert_eq!(speeds(&existing), vec![None, Some(11.0)]);
+
+        // a point an hour before the existing points and ~33 km away
+        let mut new = UserMovement::along_equator(&[(0, 0.4, "")]);
+        new.points[0].tweet_id = 10;
+        let mut movements = existing.movements;
+        update_movements(
+            &mut movements,
+            HashMap::from([(1, new)]),
+            None,
+            &Default::default(),
+            &mut Default::default(),
+        );
+        let updated = write(movements);
+        assert_eq!(speeds(&updated), vec![None, Some(33.0), Some(11.0)]);
+
+        let writer = |speed_bound| MovementJsonWriter::new(Vec::new(), speed_bound, Map::new());
+        let metadata = updated.metadata.as_ref();
+        assert!(writer(SpeedBound::Nominal)
+            .unwrap()
+            .same_speed_settings(metadata)
+            .unwrap());
+        assert!(!writer(SpeedBound::Lower)
+            .unwrap()
+            .same_speed_settings(metadata)
+            .unwrap());
+        assert!(!writer(SpeedBound::Nominal)
+            .unwrap()
+            .same_speed_settings(None)
+            .unwrap());
+    }
+}
@@ -0,0 +1,234 @@
+use crate::filter::{FilterReason, TweetFilter};
+use crate::ingest::IngestOptions;
+use crate::select::{SelectionRule, UserSelection};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// the reasons a line of input gets rejected
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// not parseable as JSON at all, for example truncated lines
+    MalformedJson,
+    /// valid JSON, but not a tweet
+    NotATweet,
+    /// the coordinates or the place of the tweet could not be converted to a point
+    InvalidGeometry,
+}
+
+impl ErrorKind {
+    pub fn from_json_error(e: &serde_json::Error) -> Self {
+        match e.classify() {
+            serde_json::error::Category::Data => Self::NotATweet,
+            _ => Self::MalformedJson,
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::MalformedJson => "malformed JSON",
+            Self::NotATweet => "no tweet",
+            Self::InvalidGeometry => "invalid geometry",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// the number of rejected lines listed per input. Only their counts are kept for the rest,
+/// the lines themselves can be written to a quarantine file.
+pub const MAX_REJECTED_LINES: usize = 100;
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct RejectedLine {
+    /// 1-based line number within the input
+    pub line: u64,
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct InputReport {
+    pub input: String,
+    pub lines: u64,
+    pub tweets: u64,
+    pub retweets: u64,
+    pub tweets_with_location: u64,
+    /// tweets located by a place coarser than the configured maximum place type
+    pub coarse_places_dropped: u64,
+    /// tweets located by a place without a type, kept despite the configured maximum place
+    /// type
+    pub untyped_places_kept: u64,
+    /// located tweets rejected by the filters
+    pub filtered: BTreeMap<FilterReason, u64>,
+    pub errors: BTreeMap<ErrorKind, u64>,
+    /// the rejected lines. The reports of the chunks list all of them, the merged report of an
+    /// input only the first [`MAX_REJECTED_LINES`].
+    pub rejected_lines: Vec<RejectedLine>,
+    /// the number of rejected lines not listed in `rejected_lines`
+    pub rejected_lines_omitted: u64,
+}
+
+impl InputReport {
+    pub fn new(input: String) -> Self {
+        Self {
+            input,
+            ..Default::default()
+        }
+    }
+
+    pub fn reject(&mut self, line: u64, kind: ErrorKind, message: String) {
+        *self.errors.entry(kind).or_default() += 1;
+        self.rejected_lines.push(RejectedLine {
+            line,
+            kind,
+            message,
+        });
+    }
+
+    pub fn error_count(&self) -> u64 {
+        self.errors.values().sum()
+    }
+
+    /// add the counts of `other` - a later part of the same input - to this report
+    pub fn merge(&mut self, other: InputReport) {
+        self.lines += other.lines;
+        self.tweets += other.tweets;
+        self.retweets += other.retweets;
+        self.tweets_with_location += other.tweets_with_location;
+        self.coarse_places_dropped += other.coarse_places_dropped;
+        self.untyped_places_kept += other.untyped_places_kept;
+        for (reason, count) in other.filtered {
+            *self.filtered.entry(reason).or_default() += count;
+        }
+        for (kind, count) in other.errors {
+            *self.errors.entry(kind).or_default() += count;
+        }
+        let listed = MAX_REJECTED_LINES.saturating_sub(self.rejected_lines.len());
+        self.rejected_lines_omitted +=
+            other.rejected_lines_omitted + other.rejected_lines.len().saturating_sub(listed) as u64;
+        self.rejected_lines
+            .extend(other.rejected_lines.into_iter().take(listed));
+    }
+}
+
+/// summary of the ingestion of all inputs
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct IngestReport {
+    /// the filters applied to the tweets
+    pub filters: TweetFilter,
+    /// the rules selecting the users
+    pub selection: UserSelection,
+    pub lines: u64,
+    pub tweets: u64,
+    pub retweets: u64,
+    pub tweets_with_location: u64,
+    pub coarse_places_dropped: u64,
+    pub untyped_places_kept: u64,
+    pub filtered: BTreeMap<FilterReason, u64>,
+    /// tweets which have been seen more than once, only the first occurrence is kept
+    pub duplicates_dropped: u64,
+    /// points removed because they could only be reached with an implausible speed
+    pub outliers_removed: u64,
+    /// the number of users kept
+    pub users: u64,
+    /// the number of previously computed users which received new points
+    pub users_updated: u64,
+    /// users dropped by each of the selection rules
+    pub users_dropped: BTreeMap<SelectionRule, u64>,
+    pub errors: BTreeMap<ErrorKind, u64>,
+    pub inputs: Vec<InputReport>,
+}
+
+impl IngestReport {
+    pub fn new(options: &IngestOptions) -> Self {
+        Self {
+            filters: options.filter.clone(),
+            selection: options.selection.clone(),
+            ..Default::default()
+        }
+    }
+
+    pub fn add_input(&mut self, input_report: InputReport) {
+        self.lines += input_report.lines;
+        self.tweets += input_report.tweets;
+        self.retweets += input_report.retweets;
+        self.tweets_with_location += input_report.tweets_with_location;
+        self.coarse_places_dropped += input_report.coarse_places_dropped;
+        self.untyped_places_kept += input_report.untyped_places_kept;
+        for (reason, count) in input_report.filtered.iter() {
+            *self.filtered.entry(*reason).or_default() += count;
+        }
+        for (kind, count) in input_report.errors.iter() {
+            *self.errors.entry(*kind).or_default() += count;
+        }
+        self.inputs.push(input_report);
+    }
+}
+
+impl fmt::Display for IngestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "read {} lines from {} inputs: {} tweets ({} retweets), {} with location",
+            self.lines,
+            self.inputs.len(),
+            self.tweets,
+            self.retweets,
+            self.tweets_with_location
+        )?;
+        if self.coarse_places_dropped > 0 {
+            write!(
+                f,
+                ", {} with too coarse places dropped",
+                self.coarse_places_dropped
+            )?;
+        }
+        if self.untyped_places_kept > 0 {
+            write!(f, ", {} with untyped places kept", self.untyped_places_kept)?;
+        }
+        for (reason, count) in self.filtered.iter() {
+            write!(f, ", {} filtered by {}", count, reason)?;
+        }
+        if self.duplicates_dropped > 0 {
+            write!(f, ", {} duplicates dropped", self.duplicates_dropped)?;
+        }
+        if self.outliers_removed > 0 {
+            write!(f, ", {} outliers removed", self.outliers_removed)?;
+        }
+        for (kind, count) in self.errors.iter() {
+            write!(f, ", {} lines with {}", count, kind)?;
+        }
+        write!(f, "; kept {} users", self.users)?;
+        if self.users_updated > 0 {
+            write!(f, ", {} users updated", self.users_updated)?;
+        }
+        for (rule, count) in self.users_dropped.iter() {
+            write!(f, ", {} users dropped for {}", count, rule)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ErrorKind, InputReport, MAX_REJECTED_LINES};
+
+    #[test]
+    fn limit_rejected_lines() {
+        let mut report = InputReport::new("input".to_string());
+        for chunk in 0..3_u64 {
+            let mut chunk_report = InputReport::default();
+            for line in 0..60 {
+                chunk_report.reject(chunk * 60 + line + 1, ErrorKind::NotATweet, String::new());
+            }
+            report.merge(chunk_report);
+        }
+        assert_eq!(report.errors[&ErrorKind::NotATweet], 180);
+        assert_eq!(report.rejected_lines.len(), MAX_REJECTED_LINES);
+        assert_eq!(report.rejected_lines[MAX_REJECTED_LINES - 1].line, 100);
+        assert_eq!(report.rejected_lines_omitted, 80);
+    }
+}
@@ -0,0 +1,217 @@
+use crate::algo::PointInTime;
+use crate::model::UserMovement;
+use chrono::Duration;
+use geo::algorithm::geodesic_distance::GeodesicDistance;
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use std::fmt;
+use uom::si::f64::Length;
+use uom::si::length::{kilometer, meter};
+
+/// rules selecting the users with meaningful trajectories
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserSelection {
+    /// the minimum number of points of a user. Less than two points do not form a movement.
+    pub min_points: usize,
+    /// the maximum number of points of a user, to exclude bots and other high-volume accounts
+    pub max_points: Option<usize>,
+    /// the minimum time between the first and the last point
+    pub min_duration: Option<Duration>,
+    /// the minimum distance covered along all points
+    pub min_distance: Option<Length>,
+    /// the maximum bot score, see [`crate::bot`]
+    pub max_bot_score: Option<f64>,
+}
+
+impl Default for UserSelection {
+    fn default() -> Self {
+        Self {
+            min_points: 2,
+            max_points: None,
+            min_duration: None,
+            min_distance: None,
+            max_bot_score: None,
+        }
+    }
+}
+
+/// the rule rejecting a user
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum SelectionRule {
+    MinPoints,
+    MaxPoints,
+    MinDuration,
+    MinDistance,
+    BotScore,
+}
+
+impl fmt::Display for SelectionRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::MinPoints => "too few points",
+            Self::MaxPoints => "too many points",
+            Self::MinDuration => "too short duration",
+            Self::MinDistance => "too short distance",
+            Self::BotScore => "likely being a bot",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl UserSelection {
+    /// check the user against the rules. Returns the first rule rejecting the user.
+    ///
+    /// The points are expected to be sorted chronologically.
+    pub fn check(&self, user_movement: &UserMovement) -> Option<SelectionRule> {
+        let points = &user_movement.points;
+        if points.len() < self.min_points.max(1) {
+            return Some(SelectionRule::MinPoints);
+        }
+        if self
+            .max_points
+            .map(|max_points| points.len() > max_points)
+            .unwrap_or(false)
+        {
+            return Some(SelectionRule::MaxPoints);
+        }
+        if let Some(min_duration) = self.min_duration {
+            let duration = points[points.len() - 1].timestamp() - points[0].timestamp();
+            if duration < min_duration {
+                return Some(SelectionRule::MinDuration);
+            }
+        }
+        if let Some(min_distance) = self.min_distance {
+            let distance = Length::new::<meter>(
+                points
+                    .windows(2)
+                    .map(|w| w[0].point().geodesic_distance(&w[1].point()))
+                    .sum(),
+            );
+            if distance < min_distance {
+                return Some(SelectionRule::MinDistance);
+            }
+        }
+        if let Some(max_bot_score) = self.max_bot_score {
+            if user_movement.bot_score().score() > max_bot_score {
+                return Some(SelectionRule::BotScore);
+            }
+        }
+        None
+    }
+}
+
+impl Serialize for UserSelection {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("UserSelection", 5)?;
+        state.serialize_field("min_points", &self.min_points)?;
+        state.serialize_field("max_points", &self.max_points)?;
+        state.serialize_field(
+            "min_duration_s",
+            &self.min_duration.map(|d| d.num_seconds()),
+        )?;
+        state.serialize_field(
+            "min_distance_km",
+            &self.min_distance.map(|l| l.get::<kilometer>()),
+        )?;
+        state.serialize_field("max_bot_score", &self.max_bot_score)?;
+        state.end()
+    }
+}
+
+/// parse a duration given as a number with an optional unit: `s`, `m`, `h` or `d`.
+/// Plain numbers are seconds.
+pub fn parse_duration(s: &str) -> eyre::Result<Duration> {
+    let s = s.trim();
+    let (value, unit_seconds) = match s.char_indices().last() {
+        Some((idx, 's')) => (&s[..idx], 1),
+        Some((idx, 'm')) => (&s[..idx], 60),
+        Some((idx, 'h')) => (&s[..idx], 60 * 60),
+        Some((idx, 'd')) => (&s[..idx], 24 * 60 * 60),
+        _ => (s, 1),
+    };
+    let value: f64 = value
+        .trim()
+        .parse()
+        .map_err(|e| eyre::eyre!("invalid duration \"{}\": {}", s, e))?;
+    if value < 0.0 {
+        return Err(eyre::eyre!("negative duration \"{}\"", s));
+    }
+    Ok(Duration::milliseconds(
+        (value * unit_seconds as f64 * 1000.0).round() as i64,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_duration, SelectionRule, UserSelection};
+    use crate::model::UserMovement;
+    use chrono::Duration;
+    use uom::si::f64::Length;
+    use uom::si::length::kilometer;
+
+    #[test]
+    fn check_selection() {
+        let selection = UserSelection {
+            min_points: 2,
+            max_points: Some(3),
+            min_duration: Some(parse_duration("1h").unwrap()),
+            min_distance: Some(Length::new::<kilometer>(50.0)),
+            max_bot_score: None,
+        };
+        assert_eq!(
+            selection.check(&UserMovement::along_equator(&[(0, 0.0, "")])),
+            Some(SelectionRule::MinPoints)
+        );
+        assert_eq!(
+            selection.check(&UserMovement::along_equator(&[
+                (0, 0.0, ""),
+                (1, 0.0, ""),
+                (2, 0.0, ""),
+                (3, 0.0, "")
+            ])),
+            Some(SelectionRule::MaxPoints)
+        );
+        assert_eq!(
+            selection.check(&UserMovement::along_equator(&[
+                (0, 0.0, ""),
+                (1800, 1.0, "")
+            ])),
+            Some(SelectionRule::MinDuration)
+        );
+        // about 11 km per 0.1 degree at the equator
+        assert_eq!(
+            selection.check(&UserMovement::along_equator(&[
+                (0, 0.0, ""),
+                (7200, 0.1, ""),
+                (9000, 0.0, "")
+            ])),
+            Some(SelectionRule::MinDistance)
+        );
+        assert_eq!(
+            selection.check(&UserMovement::along_equator(&[
+                (0, 0.0, ""),
+                (7200, 1.0, "")
+            ])),
+            None
+        );
+        assert_eq!(
+            UserSelection::default()
+                .check(&UserMovement::along_equator(&[(0, 0.0, ""), (0, 0.0, "")])),
+            None
+        );
+    }
+
+    #[test]
+    fn parse_durations() {
+        assert_eq!(parse_duration("90").unwrap(), Duration::seconds(90));
+        assert_eq!(parse_duration("30m").unwrap(), Duration::minutes(30));
+        assert_eq!(parse_duration("1.5h").unwrap(), Duration::minutes(90));
+        assert_eq!(parse_duration("2d").unwrap(), Duration::days(2));
+        assert!(parse_duration("2w").is_err());
+        assert!(parse_duration("-1h").is_err());
+    }
+}
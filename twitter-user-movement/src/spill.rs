@@ -0,0 +1,273 @@
+use crate::ingest::{merge_movements, Movements};
+use crate::input::{Compression, Input};
+use crate::model::UserMovement;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::mem::{size_of, size_of_val};
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// number of buckets used when the size of the input can not be estimated
+const DEFAULT_BUCKETS: usize = 64;
+
+/// factor to estimate the size of the movements in memory from the size of uncompressed JSONL.
+/// The movements only keep located tweets and a few of their fields.
+const MEMORY_PER_INPUT_BYTE: f64 = 0.1;
+
+/// the assumed compression ratio of compressed inputs
+const COMPRESSION_RATIO: f64 = 8.0;
+
+/// the maximum number of buckets written in one pass over the inputs. Each bucket keeps a
+/// file open while the inputs are read, so this stays well below the usual limits of open
+/// files. Budgets needing more buckets are met by reading the inputs several times.
+pub const MAX_BUCKETS: usize = 128;
+
+#[derive(Debug, Clone)]
+pub struct SpillOptions {
+    /// the memory to use for the movements before they are written to disk, and
+    /// the memory a single bucket is expected to need when it is read back.
+    pub memory_budget_bytes: u64,
+
+    /// the total number of buckets to partition the users into. Estimated from the sizes of
+    /// the inputs when not set.
+    pub buckets: Option<usize>,
+
+    /// the directory to create the bucket files in. The default is the temporary directory
+    /// of the system.
+    pub directory: Option<PathBuf>,
+}
+
+/// how the users are partitioned into buckets
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BucketPlan {
+    /// the number of buckets written in each pass, at most [`MAX_BUCKETS`]
+    pub buckets: usize,
+    /// the number of passes over the inputs, each pass keeps the users of its own buckets
+    pub passes: usize,
+}
+
+impl BucketPlan {
+    /// the pass and the bucket within the pass of a user
+    pub fn bucket_of(&self, user_id: u64) -> (usize, usize) {
+        let bucket = (user_id % (self.buckets * self.passes) as u64) as usize;
+        (bucket / self.buckets, bucket % self.buckets)
+    }
+}
+
+impl SpillOptions {
+    /// partition the users into as many buckets as needed to meet the memory budget.
+    ///
+    /// Fails when more than [`MAX_BUCKETS`] are needed while reading from stdin, which
+    /// can not be read several times.
+    pub fn bucket_plan(&self, inputs: &[Input]) -> eyre::Result<BucketPlan> {
+        let buckets = self.bucket_count(inputs)?;
+        let passes = buckets.div_ceil(MAX_BUCKETS);
+        if passes > 1 && inputs.iter().any(|input| matches!(input, Input::Stdin)) {
+            return Err(eyre::eyre!(
+                "the memory budget of {} bytes needs {} buckets, more than the {} which can \
+                 be written in a single pass over stdin. Raise the memory budget or read \
+                 the tweets from files",
+                self.memory_budget_bytes,
+                buckets,
+                MAX_BUCKETS
+            ));
+        }
+        Ok(BucketPlan {
+            buckets: buckets.min(MAX_BUCKETS),
+            passes,
+        })
+    }
+
+    fn bucket_count(&self, inputs: &[Input]) -> eyre::Result<usize> {
+        if let Some(buckets) = self.buckets {
+            return Ok(buckets.max(1));
+        }
+        let mut estimated_bytes = 0.0;
+        let mut min_buckets = 1;
+        for input in inputs {
+            match input {
+                Input::Stdin => min_buckets = DEFAULT_BUCKETS,
+                Input::File(path) => {
+                    let len = path.metadata()?.len() as f64;
+                    estimated_bytes += match Compression::from_extension(path) {
+                        Some(Compression::None) => len,
+                        _ => len * COMPRESSION_RATIO,
+                    } * MEMORY_PER_INPUT_BYTE;
+                }
+            }
+        }
+        let buckets = (estimated_bytes / self.memory_budget_bytes.max(1) as f64).ceil() as usize;
+        Ok(buckets.max(min_buckets))
+    }
+}
+
+/// partitions the users of one pass of a [`BucketPlan`] into bucket files on disk by their
+/// id. The users of the other passes are dropped.
+pub struct BucketWriter {
+    directory: TempDir,
+    writers: Vec<BufWriter<File>>,
+    plan: BucketPlan,
+    pass: usize,
+    buffered: Movements,
+    buffered_bytes: u64,
+    memory_budget_bytes: u64,
+}
+
+impl BucketWriter {
+    pub fn new(options: &SpillOptions, plan: BucketPlan, pass: usize) -> eyre::Result<Self> {
+        let directory = match options.directory.as_ref() {
+            Some(dir) => tempfile::Builder::new()
+                .prefix("twitter-user-movement")
+                .tempdir_in(dir)?,
+            None => tempfile::Builder::new()
+                .prefix("twitter-user-movement")
+                .tempdir()?,
+        };
+        let writers = (0..plan.buckets)
+            .map(|i| {
+                Ok(BufWriter::new(File::create(bucket_path(
+                    directory.path(),
+                    i,
+                ))?))
+            })
+            .collect::<eyre::Result<Vec<_>>>()?;
+        Ok(Self {
+            directory,
+            writers,
+            plan,
+            pass,
+            buffered: Movements::new(),
+            buffered_bytes: 0,
+            memory_budget_bytes: options.memory_budget_bytes,
+        })
+    }
+
+    /// add movements, spilling all buffered movements to disk when the memory budget is
+    /// exceeded.
+    pub fn push(&mut self, mut movements: Movements) -> eyre::Result<()> {
+        movements.retain(|user_id, _| self.plan.bucket_of(*user_id).0 == self.pass);
+        self.buffered_bytes += movements.values().map(estimated_size).sum::<u64>();
+        merge_movements(&mut self.buffered, movements);
+        if self.buffered_bytes > self.memory_budget_bytes {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    fn spill(&mut self) -> eyre::Result<()> {
+        for (user_id, user_movement) in self.buffered.drain() {
+            let writer = &mut self.writers[self.plan.bucket_of(user_id).1];
+            serde_json::to_writer(&mut *writer, &user_movement)?;
+            writer.write_all(b"\n")?;
+        }
+        self.buffered_bytes = 0;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> eyre::Result<Buckets> {
+        self.spill()?;
+        for writer in self.writers.iter_mut() {
+            writer.flush()?;
+        }
+        Ok(Buckets {
+            count: self.writers.len(),
+            directory: self.directory,
+        })
+    }
+}
+
+/// the bucket files written by a [`BucketWriter`]. The files are removed on drop.
+pub struct Buckets {
+    directory: TempDir,
+    count: usize,
+}
+
+impl Buckets {
+    /// the number of buckets
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// read a bucket, merging the parts of the users spilled at different times in the
+    /// order they were written.
+    pub fn read(&self, bucket: usize) -> eyre::Result<Movements> {
+        let mut movements = Movements::new();
+        let reader = BufReader::new(File::open(bucket_path(self.directory.path(), bucket))?);
+        for line in reader.lines() {
+            let user_movement: UserMovement = serde_json::from_str(&line?)?;
+            merge_movements(
+                &mut movements,
+                Movements::from([(user_movement.user_id, user_movement)]),
+            );
+        }
+        Ok(movements)
+    }
+}
+
+fn bucket_path(directory: &Path, bucket: usize) -> PathBuf {
+    directory.join(format!("bucket-{:05}.jsonl", bucket))
+}
+
+/// rough estimation of the memory used by the movement of a user
+fn estimated_size(user_movement: &UserMovement) -> u64 {
+    let points: usize = user_movement
+        .points
+        .iter()
+        .map(|mp| {
+            size_of_val(mp)
+                + mp.text.len()
+                + mp.lang.as_ref().map(|s| s.len()).unwrap_or(0)
+                + mp.place
+                    .as_ref()
+                    .map(|pi| {
+                        pi.id.as_ref().map(|s| s.len()).unwrap_or(0)
+                            + pi.full_name.as_ref().map(|s| s.len()).unwrap_or(0)
+                    })
+                    .unwrap_or(0)
+        })
+        .sum();
+    (size_of::<UserMovement>()
+        + user_movement.user_name.len()
+        + user_movement.user_screen_name.len()
+        + points) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BucketPlan, SpillOptions, MAX_BUCKETS};
+    use crate::input::Input;
+    use std::io::Write;
+
+    #[test]
+    fn cap_buckets_per_pass() {
+        let mut f = tempfile::Builder::new()
+            .suffix(".jsonl")
+            .tempfile()
+            .unwrap();
+        f.write_all(&[b' '; 100_000]).unwrap();
+        let inputs = vec![Input::File(f.path().to_path_buf())];
+        let options = |memory_budget_bytes: u64| SpillOptions {
+            memory_budget_bytes,
+            buckets: None,
+            directory: None,
+        };
+
+        // 10 kB of movements estimated
+        assert_eq!(
+            options(1000).bucket_plan(&inputs).unwrap(),
+            BucketPlan {
+                buckets: 10,
+                passes: 1
+            }
+        );
+        let plan = options(1).bucket_plan(&inputs).unwrap();
+        assert_eq!(plan.buckets, MAX_BUCKETS);
+        assert_eq!(plan.passes, 79);
+        assert_eq!(plan.bucket_of(MAX_BUCKETS as u64 + 3), (1, 3));
+
+        // stdin can only be read once
+        let with_stdin = vec![Input::File(f.path().to_path_buf()), Input::Stdin];
+        assert!(options(1).bucket_plan(&with_stdin).is_err());
+        assert_eq!(options(1000).bucket_plan(&with_stdin).unwrap().passes, 1);
+    }
+}
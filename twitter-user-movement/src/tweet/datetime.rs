@@ -1,15 +1,30 @@
 // from tweet 0.3 crate
 
-use chrono::{DateTime, TimeZone, Utc};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Deserializer};
 
 const FORMAT: &str = "%a %b %e %T %z %Y";
 
+/// the epoch of the twitter snowflake ids in milliseconds since the unix epoch
+const SNOWFLAKE_EPOCH_MS: i64 = 1288834974657;
+
+/// the first tweet id generated by snowflake, older ids are sequential numbers
+const SNOWFLAKE_MIN_ID: u64 = 29700859247;
+
+/// the millisecond timestamp encoded in a snowflake id. `None` for pre-snowflake ids.
+pub fn snowflake_timestamp(id: u64) -> Option<DateTime<Utc>> {
+    if id < SNOWFLAKE_MIN_ID {
+        return None;
+    }
+    DateTime::from_timestamp_millis((id >> 22) as i64 + SNOWFLAKE_EPOCH_MS)
+}
+
 pub fn datefmt_de<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
 where
     D: Deserializer<'de>,
 {
     let s = String::deserialize(deserializer)?;
-    Utc.datetime_from_str(&s, FORMAT)
+    DateTime::parse_from_str(&s, FORMAT)
+        .map(|dt| dt.with_timezone(&Utc))
         .map_err(serde::de::Error::custom)
 }
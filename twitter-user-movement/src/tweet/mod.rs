@@ -1,15 +1,20 @@
+use chrono::Duration;
 use chrono::{DateTime, Utc};
-use datetime::datefmt_de;
+use datetime::{datefmt_de, snowflake_timestamp};
 use geo::centroid::Centroid;
+use geo::prelude::GeodesicDistance;
 use geo_types::{Point, Polygon};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
 
 mod datetime;
+pub mod v2;
 
 #[derive(Deserialize)]
 pub struct Tweet {
     pub id: u64,
     pub user: User,
+    #[serde(alias = "full_text")]
     pub text: String,
     pub in_reply_to_user_id: Option<u64>,
     pub lang: Option<String>,
@@ -20,9 +25,47 @@ pub struct Tweet {
     pub coordinates: Option<geojson::Geometry>,
 
     pub public_metrics: Option<PublicMetrics>,
+
+    /// the original tweet when this tweet is a retweet
+    pub retweeted_status: Option<Box<Tweet>>,
+    /// the tweet quoted by this tweet
+    pub quoted_status: Option<Box<Tweet>>,
 }
 
 impl Tweet {
+    pub fn is_retweet(&self) -> bool {
+        self.retweeted_status.is_some()
+    }
+
+    /// the time the tweet was created with millisecond precision when it can be derived from
+    /// the snowflake id. Falls back to the second precision `created_at` for pre-snowflake ids
+    /// and for ids which do not match `created_at`.
+    pub fn timestamp(&self) -> DateTime<Utc> {
+        self.id_timestamp().unwrap_or(self.created_at)
+    }
+
+    /// whether [`Tweet::timestamp`] has millisecond precision because it was derived from
+    /// the snowflake id
+    pub fn has_exact_timestamp(&self) -> bool {
+        self.id_timestamp().is_some()
+    }
+
+    fn id_timestamp(&self) -> Option<DateTime<Utc>> {
+        snowflake_timestamp(self.id)
+            .filter(|ts| (*ts - self.created_at).abs() < Duration::seconds(1))
+    }
+
+    /// the place type of the location when the tweet is only located by its place.
+    /// Places without a type are treated as [`PlaceType::Unknown`].
+    ///
+    /// `None` for tweets with exact coordinates or without any location.
+    pub fn inexact_place_type(&self) -> Option<PlaceType> {
+        match (self.coordinates.as_ref(), self.place.as_ref()) {
+            (None, Some(place)) => Some(place.place_type.unwrap_or(PlaceType::Unknown)),
+            _ => None,
+        }
+    }
+
     pub fn geo_point(&self) -> eyre::Result<Option<(Point<f64>, bool)>> {
         if let Some(gjg) = self.coordinates.as_ref() {
             Ok(Some((gjg.value.clone().try_into()?, true)))
@@ -33,6 +76,42 @@ impl Tweet {
             Ok(None)
         }
     }
+
+    /// radius in meters around the point returned by [`Tweet::geo_point`] within which
+    /// the actual location lies.
+    ///
+    /// Zero for exact locations, the distance to the farthest corner of the bounding box
+    /// for locations derived from the place.
+    pub fn uncertainty_radius_m(&self, point: &Point<f64>) -> eyre::Result<f64> {
+        match (self.coordinates.as_ref(), self.place.as_ref()) {
+            (None, Some(place)) => {
+                let poly: Polygon<f64> = place.bounding_box.value.clone().try_into()?;
+                Ok(poly
+                    .exterior()
+                    .points()
+                    .map(|corner| point.geodesic_distance(&corner))
+                    .fold(0.0, f64::max))
+            }
+            _ => Ok(0.0),
+        }
+    }
+}
+
+/// parse a line of JSONL containing either a v1.1 tweet or a v2 response.
+///
+/// The line is parsed as v1.1 tweet first, v2 responses - recognized by their `data`
+/// envelope - may contain multiple tweets. The error of the v1.1 attempt is returned when
+/// the line is neither.
+pub fn parse_line(line: &[u8]) -> Result<Vec<Tweet>, serde_json::Error> {
+    match serde_json::from_slice::<Tweet>(line) {
+        Ok(tweet) => Ok(vec![tweet]),
+        // syntax errors would be the same for a v2 response
+        Err(e) if !e.is_data() => Err(e),
+        Err(e) => match serde_json::from_slice::<v2::Response>(line) {
+            Ok(response) => Ok(response.into_tweets()),
+            Err(_) => Err(e),
+        },
+    }
 }
 
 #[derive(Deserialize)]
@@ -44,10 +123,47 @@ pub struct User {
 
 #[derive(Deserialize)]
 pub struct Place {
+    pub id: Option<String>,
+    pub place_type: Option<PlaceType>,
+    pub full_name: Option<String>,
+    pub country_code: Option<String>,
     pub bounding_box: geojson::Geometry,
 }
 
-#[derive(Deserialize)]
+/// the types of places, ordered from the most precise to the coarsest
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaceType {
+    Poi,
+    Neighborhood,
+    City,
+    Admin,
+    Country,
+    /// places without a type or with a type unknown to this crate. Their coarseness is
+    /// unknown, so they are not dropped by a maximum place type.
+    #[serde(other)]
+    Unknown,
+}
+
+impl FromStr for PlaceType {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "poi" => Ok(Self::Poi),
+            "neighborhood" => Ok(Self::Neighborhood),
+            "city" => Ok(Self::City),
+            "admin" => Ok(Self::Admin),
+            "country" => Ok(Self::Country),
+            _ => Err(eyre::eyre!(
+                "unknown place type \"{}\", expected poi, neighborhood, city, admin or country",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
 pub struct PublicMetrics {
     pub retweet_count: i64,
     pub reply_count: i64,
@@ -57,8 +173,8 @@ pub struct PublicMetrics {
 
 #[cfg(test)]
 mod tests {
-    use crate::tweet::Tweet;
-    use std::fs::File;
+    use crate::tweet::{parse_line, PlaceType, Tweet};
+    use std::fs::{read_to_string, File};
 
     #[test]
     fn parse_tweet() {
@@ -67,8 +183,53 @@ mod tests {
         )
         .unwrap();
         assert_eq!(tweet.id, 1307025659294674945);
-        //assert_eq!(tweet.created_at)
+        assert_eq!(tweet.created_at.timestamp(), 1600454175);
+        assert_eq!(tweet.timestamp().timestamp_millis(), 1600454175195);
+        assert!(tweet.has_exact_timestamp());
         assert!(tweet.coordinates.is_some());
         assert!(tweet.geo_point().is_ok());
+
+        let place = tweet.place.unwrap();
+        assert_eq!(place.id.as_deref(), Some("01fbe706f872cb32"));
+        assert_eq!(place.place_type, Some(PlaceType::City));
+        assert_eq!(place.full_name.as_deref(), Some("Washington, DC"));
+        assert_eq!(place.country_code.as_deref(), Some("US"));
+    }
+
+    #[test]
+    fn timestamp_fallback() {
+        let mut tweet: Tweet = serde_json::from_reader(
+            File::open(format!("{}/../data/tweet.json", env!("CARGO_MANIFEST_DIR"))).unwrap(),
+        )
+        .unwrap();
+
+        // pre-snowflake id
+        tweet.id = 12345;
+        assert_eq!(tweet.timestamp(), tweet.created_at);
+        assert!(!tweet.has_exact_timestamp());
+
+        // the id does not match the creation time
+        tweet.id = 1307025659294674945;
+        tweet.created_at -= chrono::Duration::days(1);
+        assert_eq!(tweet.timestamp(), tweet.created_at);
+        assert!(!tweet.has_exact_timestamp());
+    }
+
+    #[test]
+    fn parse_line_v1_and_v2() {
+        for (filename, n_tweets) in [("tweet.json", 1), ("tweet_v2.json", 2)] {
+            let json: serde_json::Value = serde_json::from_str(
+                &read_to_string(format!(
+                    "{}/../data/{}",
+                    env!("CARGO_MANIFEST_DIR"),
+                    filename
+                ))
+                .unwrap(),
+            )
+            .unwrap();
+            let tweets = parse_line(json.to_string().as_bytes()).unwrap();
+            assert_eq!(tweets.len(), n_tweets);
+            assert_eq!(tweets[0].id, 1307025659294674945);
+        }
     }
 }
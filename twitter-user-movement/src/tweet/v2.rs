@@ -0,0 +1,302 @@
+//! Twitter API v2 payloads
+//!
+//! v2 responses wrap the tweets in a `data` envelope and move users and places into
+//! `includes`. The tweets are converted to the v1.1 shaped [`Tweet`] after resolving
+//! the author, the place and the referenced tweets from the includes.
+
+use crate::tweet::{Place, PlaceType, PublicMetrics, Tweet, User};
+use chrono::{DateTime, Utc};
+use geojson::{Geometry, Value};
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+#[derive(Deserialize)]
+pub struct Response {
+    pub data: OneOrMany<TweetV2>,
+    #[serde(default)]
+    pub includes: Includes,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum OneOrMany<T> {
+    One(T),
+    Many(Vec<T>),
+}
+
+#[derive(Deserialize, Default)]
+pub struct Includes {
+    #[serde(default)]
+    pub users: Vec<UserV2>,
+    #[serde(default)]
+    pub places: Vec<PlaceV2>,
+    /// referenced tweets
+    #[serde(default)]
+    pub tweets: Vec<TweetV2>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct TweetV2 {
+    #[serde(deserialize_with = "u64_from_str")]
+    pub id: u64,
+    #[serde(deserialize_with = "u64_from_str")]
+    pub author_id: u64,
+    pub text: String,
+    #[serde(default, deserialize_with = "option_u64_from_str")]
+    pub in_reply_to_user_id: Option<u64>,
+    pub lang: Option<String>,
+
+    /// ISO-8601
+    pub created_at: DateTime<Utc>,
+    pub geo: Option<GeoV2>,
+
+    pub public_metrics: Option<PublicMetrics>,
+
+    #[serde(default)]
+    pub referenced_tweets: Vec<ReferencedTweet>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ReferencedTweet {
+    /// `retweeted`, `quoted` or `replied_to`
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(deserialize_with = "u64_from_str")]
+    pub id: u64,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct GeoV2 {
+    pub place_id: Option<String>,
+    pub coordinates: Option<Geometry>,
+}
+
+#[derive(Deserialize)]
+pub struct UserV2 {
+    #[serde(deserialize_with = "u64_from_str")]
+    pub id: u64,
+    pub name: String,
+    pub username: String,
+}
+
+#[derive(Deserialize)]
+pub struct PlaceV2 {
+    pub id: String,
+    pub place_type: Option<PlaceType>,
+    pub full_name: Option<String>,
+    pub country_code: Option<String>,
+    pub geo: Option<PlaceGeoV2>,
+}
+
+/// the GeoJSON feature of the place. Only the bbox is of interest.
+#[derive(Deserialize)]
+pub struct PlaceGeoV2 {
+    /// `[west, south, east, north]`
+    pub bbox: Option<[f64; 4]>,
+}
+
+impl Response {
+    /// convert the tweets of the response to [`Tweet`]s, resolving authors, places and
+    /// retweeted or quoted tweets from the includes.
+    ///
+    /// Authors missing from the includes get empty names.
+    pub fn into_tweets(self) -> Vec<Tweet> {
+        let resolver = Resolver::new(self.includes);
+        let tweets = match self.data {
+            OneOrMany::One(tweet) => vec![tweet],
+            OneOrMany::Many(tweets) => tweets,
+        };
+        tweets
+            .into_iter()
+            .map(|tweet| resolver.resolve(tweet, true))
+            .collect()
+    }
+}
+
+struct Resolver {
+    users: HashMap<u64, UserV2>,
+    places: HashMap<String, PlaceV2>,
+    tweets: HashMap<u64, TweetV2>,
+}
+
+impl Resolver {
+    fn new(includes: Includes) -> Self {
+        Self {
+            users: includes
+                .users
+                .into_iter()
+                .map(|user| (user.id, user))
+                .collect(),
+            places: includes
+                .places
+                .into_iter()
+                .map(|place| (place.id.clone(), place))
+                .collect(),
+            tweets: includes
+                .tweets
+                .into_iter()
+                .map(|tweet| (tweet.id, tweet))
+                .collect(),
+        }
+    }
+
+    /// convert to a [`Tweet`]. Referenced tweets are only resolved when
+    /// `follow_references` is set, so only one level of references is followed.
+    fn resolve(&self, tweet: TweetV2, follow_references: bool) -> Tweet {
+        let referenced = |kind: &str| {
+            if !follow_references {
+                return None;
+            }
+            tweet
+                .referenced_tweets
+                .iter()
+                .find(|rt| rt.kind == kind)
+                .and_then(|rt| self.tweets.get(&rt.id))
+                .map(|referenced| Box::new(self.resolve(referenced.clone(), false)))
+        };
+        let retweeted_status = referenced("retweeted");
+        let quoted_status = referenced("quoted");
+
+        let user = self
+            .users
+            .get(&tweet.author_id)
+            .map(|user| User {
+                id: user.id,
+                name: user.name.clone(),
+                screen_name: user.username.clone(),
+            })
+            .unwrap_or_else(|| User {
+                id: tweet.author_id,
+                name: String::new(),
+                screen_name: String::new(),
+            });
+
+        let (coordinates, place_id) = match tweet.geo {
+            Some(geo) => (geo.coordinates, geo.place_id),
+            None => (None, None),
+        };
+        let place = place_id
+            .and_then(|place_id| self.places.get(&place_id))
+            .and_then(|place| place.to_place());
+
+        Tweet {
+            id: tweet.id,
+            user,
+            text: tweet.text,
+            in_reply_to_user_id: tweet.in_reply_to_user_id,
+            lang: tweet.lang,
+            created_at: tweet.created_at,
+            place,
+            coordinates,
+            public_metrics: tweet.public_metrics,
+            retweeted_status,
+            quoted_status,
+        }
+    }
+}
+
+impl PlaceV2 {
+    fn to_place(&self) -> Option<Place> {
+        let [west, south, east, north] = self.geo.as_ref()?.bbox?;
+        Some(Place {
+            id: Some(self.id.clone()),
+            place_type: self.place_type,
+            full_name: self.full_name.clone(),
+            country_code: self.country_code.clone(),
+            bounding_box: Geometry::new(Value::Polygon(vec![vec![
+                vec![west, south],
+                vec![east, south],
+                vec![east, north],
+                vec![west, north],
+                vec![west, south],
+            ]])),
+        })
+    }
+}
+
+/// v2 transmits all ids as strings
+fn u64_from_str<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    u64::from_str(&s).map_err(serde::de::Error::custom)
+}
+
+fn option_u64_from_str<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|s| u64::from_str(&s).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Response;
+    use crate::tweet::PlaceType;
+    use std::fs::File;
+
+    #[test]
+    fn parse_response() {
+        let response: Response = serde_json::from_reader(
+            File::open(format!(
+                "{}/../data/tweet_v2.json",
+                env!("CARGO_MANIFEST_DIR")
+            ))
+            .unwrap(),
+        )
+        .unwrap();
+        let tweets = response.into_tweets();
+        assert_eq!(tweets.len(), 2);
+        assert_eq!(tweets[0].id, 1307025659294674945);
+        assert_eq!(tweets[0].user.screen_name, "TwitterDev");
+        assert_eq!(tweets[0].in_reply_to_user_id, Some(2244994945));
+        assert_eq!(tweets[0].created_at.timestamp(), 1600454175);
+
+        let (_, is_exact_location) = tweets[0].geo_point().unwrap().unwrap();
+        assert!(is_exact_location);
+
+        // resolved from the includes
+        assert_eq!(tweets[1].inexact_place_type(), Some(PlaceType::City));
+        let (point, is_exact_location) = tweets[1].geo_point().unwrap().unwrap();
+        assert!(!is_exact_location);
+        assert!((point.x() - -77.014576).abs() < 1e-6);
+        // half of the diagonal of the bbox
+        let radius = tweets[1].uncertainty_radius_m(&point).unwrap();
+        assert!((radius - 14_540.0).abs() < 100.0);
+        assert_eq!(tweets[0].uncertainty_radius_m(&point).unwrap(), 0.0);
+    }
+
+    #[test]
+    fn resolve_retweets() {
+        let response: Response = serde_json::from_value(serde_json::json!({
+            "data": {
+                "id": "2",
+                "text": "RT @a: original",
+                "author_id": "20",
+                "created_at": "2020-09-18T18:36:15.000Z",
+                "referenced_tweets": [{"type": "retweeted", "id": "1"}]
+            },
+            "includes": {
+                "tweets": [{
+                    "id": "1",
+                    "text": "original",
+                    "author_id": "10",
+                    "created_at": "2020-09-18T17:36:15.000Z",
+                    "geo": {"coordinates": {"type": "Point", "coordinates": [1.0, 2.0]}}
+                }]
+            }
+        }))
+        .unwrap();
+        let tweets = response.into_tweets();
+        assert_eq!(tweets.len(), 1);
+        assert!(tweets[0].is_retweet());
+        assert!(tweets[0].quoted_status.is_none());
+        let original = tweets[0].retweeted_status.as_ref().unwrap();
+        assert_eq!(original.user.id, 10);
+        assert!(original.geo_point().unwrap().is_some());
+    }
+}